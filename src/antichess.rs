@@ -0,0 +1,43 @@
+use crate::moves::*;
+use crate::position::Position;
+use crate::variant::*;
+
+// Antichess, also known as losing chess: captures are compulsory, there's no
+// check or checkmate, the king is just another piece which can be captured
+// and promoted to, and there's no castling.  The first player to lose all
+// their pieces, or to have no legal move, wins.
+#[derive(Clone, Copy)]
+pub struct Antichess;
+
+fn is_capture(pos: &Position, mv: u16) -> bool {
+    mv_get_kind(mv) == MOVE_ENPASSANT || pos.color_on(mv_get_dst(mv)) == pos.enemy()
+}
+
+impl Variant for Antichess {
+    // With no check, every pseudo-legal move is legal.
+    const PSEUDO_LEGAL: bool = true;
+    const KING_PROMOTION: bool = true;
+
+    // If anything can be captured, something must be.
+    fn filter_moves(pos: &Position, moves: &mut MoveList) {
+        if moves.iter().any(|&mv| is_capture(pos, mv)) {
+            moves.retain(|mv| is_capture(pos, mv));
+        }
+    }
+
+    fn variant_outcome(pos: &Position) -> Option<Outcome> {
+        if pos.our_pieces() == 0 {
+            Some(Outcome::Win(pos.us()))
+        } else {
+            None
+        }
+    }
+
+    fn no_moves_outcome(pos: &Position) -> Outcome {
+        Outcome::Win(pos.us())
+    }
+
+    fn parse_fen_extensions(pos: &mut Position, _ext: &FenExtensions) {
+        pos.castle = 0;
+    }
+}
@@ -0,0 +1,144 @@
+use crate::attack::*;
+use crate::bitboard::*;
+use crate::moves::*;
+use crate::position::*;
+use crate::things::*;
+use crate::variant::*;
+
+// Atomic chess: a capture sets off an explosion on the destination square,
+// removing the capturing piece along with every piece other than a pawn on
+// the surrounding squares.  Blowing up the enemy king wins the game, so a
+// king can never capture, and kings standing next to each other can't be in
+// check since capturing either one would blow up both.
+#[derive(Clone, Copy)]
+pub struct Atomic;
+
+// Is sq attacked by the given color?  Squares next to that color's own king
+// can't be, as a capture there would blow up the attacker's king too.
+fn attacked_by(pos: &Position, sq: u8, color: Color, occupancy: u64) -> bool {
+    let king = pos.king(color);
+    if king != 0 && king_attacks(king) & bb_from_sq(sq) != 0 {
+        return false;
+    }
+    pos.attackers_to(sq, occupancy) & pos.color_pieces(color) != 0
+}
+
+// The squares an explosion on dst can reach.  The Undo records what it
+// cleared in four bits per square of the area, in square order: zero for a
+// square left alone, or else one more than the piece, with the color in the
+// top bit.  All nine squares fit in one word, which keeps the Undo small for
+// the other variants that don't use it.
+fn blast_area(dst: u8) -> u64 {
+    king_attacks_from(dst) | bb_from_sq(dst)
+}
+
+fn pack_exploded(piece: Piece, color: Color) -> u64 {
+    (piece as u64 + 1) | (color as u64) << 3
+}
+
+fn unpack_exploded(bits: u64) -> Option<(Piece, Color)> {
+    let color = if bits & 0x8 == 0 { White } else { Black };
+    match bits & 0x7 {
+        0 => None,
+        piece => Some(([Pawn, Knight, Bishop, Rook, Queen, King][piece as usize - 1], color)),
+    }
+}
+
+fn in_check(pos: &Position, color: Color) -> bool {
+    let king = pos.king(color);
+    king != 0 && attacked_by(pos, bb_lsb(king), opposite(color), pos.occupancy())
+}
+
+impl Variant for Atomic {
+    // The pin and check logic can't cope with explosions, so each move is
+    // played out to see what's left standing.
+    const PSEUDO_LEGAL: bool = true;
+
+    fn is_legal(pos: &Position, mv: u16) -> bool {
+        let us = pos.us();
+        let them = pos.enemy();
+
+        if mv_get_kind(mv) == MOVE_CASTLE {
+            // The king can't castle out of, through or into check.
+            let king_sq = mv_get_src(mv);
+            let rook_sq = mv_get_dst(mv);
+            let (king_dst, rook_dst) = castle_destinations(castle_right(us, king_sq, rook_sq));
+            let king = bb_from_sq(king_sq);
+            let occ = pos.occupancy() & !king;
+            let mut path = king | bb_between(king_sq, king_dst);
+            while path != 0 {
+                if attacked_by(pos, bb_pop(&mut path), them, occ) {
+                    return false;
+                }
+            }
+            let occ = (occ & !bb_from_sq(rook_sq)) | bb_from_sq(rook_dst) | bb_from_sq(king_dst);
+            return !attacked_by(pos, king_dst, them, occ);
+        }
+
+        let next = pos.play_variant::<Atomic>(mv);
+        if next.king(us) == 0 {
+            // Our own king went up in the explosion.
+            return false;
+        }
+        if next.king(them) == 0 {
+            // Blowing up their king wins, even if ours is left in check.
+            return true;
+        }
+        !in_check(&next, us)
+    }
+
+    fn after_move(pos: &mut Position, undo: &mut Undo) {
+        let kind = mv_get_kind(undo.mv);
+        if undo.captured == NoPiece && kind != MOVE_ENPASSANT {
+            return;
+        }
+
+        let dst = mv_get_dst(undo.mv);
+        let pawns = pos.pieces(Pawn, White) | pos.pieces(Pawn, Black);
+        let blast = (king_attacks_from(dst) & pos.occupancy() & !pawns) | bb_from_sq(dst);
+
+        // Remember what was blown up so it can be put back.
+        undo.exploded = 0;
+        let mut area = blast_area(dst);
+        let mut i = 0;
+        while area != 0 {
+            let sq = bb_pop(&mut area);
+            if blast & bb_from_sq(sq) != 0 {
+                undo.exploded |= pack_exploded(pos.piece_on(sq), pos.color_on(sq)) << (4 * i);
+                pos.clear_sq(sq);
+                pos.update_castle_rights(sq);
+            }
+            i += 1;
+        }
+    }
+
+    fn before_unmake(pos: &mut Position, undo: &Undo) {
+        let mut area = blast_area(mv_get_dst(undo.mv));
+        let mut i = 0;
+        while area != 0 {
+            let sq = bb_pop(&mut area);
+            if let Some((piece, color)) = unpack_exploded(undo.exploded >> (4 * i)) {
+                pos.put_piece(sq, piece, color);
+            }
+            i += 1;
+        }
+    }
+
+    fn variant_outcome(pos: &Position) -> Option<Outcome> {
+        if pos.our_king() == 0 {
+            Some(Outcome::Win(pos.enemy()))
+        } else if pos.their_king() == 0 {
+            Some(Outcome::Win(pos.us()))
+        } else {
+            None
+        }
+    }
+
+    fn no_moves_outcome(pos: &Position) -> Outcome {
+        if in_check(pos, pos.us()) {
+            Outcome::Win(pos.enemy())
+        } else {
+            Outcome::Draw
+        }
+    }
+}
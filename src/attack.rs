@@ -5,23 +5,23 @@ use super::things::*;
 
 // TODO: reclaim pawn space
 // TODO: make this 2D
-static mut EMPTY_BOARD_ATTACKS: &'static mut [u64] = &mut [0; NUM_PIECES * NUM_SQUARES];
+static mut EMPTY_BOARD_ATTACKS: &mut [u64] = &mut [0; NUM_PIECES * NUM_SQUARES];
 
 // This technique is used to avoid sliding rooks, bishops, and queens around
 // during move generation.  Attacks for rooks & bishops are pre-generated based
 // their source square and the obstacle(s) their attack ray(s) may encounter.
 // This is implemented using a well-known algorithm described at:
 // https://www.chessprogramming.org/Magic_Bitboards
-static mut BISHOP_MASKS: &'static mut [u64] = &mut [0; 64];
-static mut BISHOP_SHIFT: &'static mut [u64] = &mut [0; 64];
-static mut BISHOP_ATTACK_TABLE: &'static mut [u64] = &mut [0; 5248];
-static mut BISHOP_ATTACK_INDEX: &'static mut [usize] = &mut [0; 64];
-static mut ROOK_MASKS: &'static mut [u64] = &mut [0; 64];
-static mut ROOK_SHIFT: &'static mut [u64] = &mut [0; 64];
-static mut ROOK_ATTACK_TABLE: &'static mut [u64] = &mut [0; 102400];
-static mut ROOK_ATTACK_INDEX: &'static mut [usize] = &mut [0; 64];
-
-pub const ROOK_MAGIC: [u64; NUM_SQUARES as usize] = [
+static mut BISHOP_MASKS: &mut [u64] = &mut [0; 64];
+static mut BISHOP_SHIFT: &mut [u64] = &mut [0; 64];
+static mut BISHOP_ATTACK_TABLE: &mut [u64] = &mut [0; 5248];
+static mut BISHOP_ATTACK_INDEX: &mut [usize] = &mut [0; 64];
+static mut ROOK_MASKS: &mut [u64] = &mut [0; 64];
+static mut ROOK_SHIFT: &mut [u64] = &mut [0; 64];
+static mut ROOK_ATTACK_TABLE: &mut [u64] = &mut [0; 102400];
+static mut ROOK_ATTACK_INDEX: &mut [usize] = &mut [0; 64];
+
+pub const ROOK_MAGIC: [u64; NUM_SQUARES] = [
     0xd080044000148022,
     0x2440002008401002,
     0x5200104008802200,
@@ -88,7 +88,7 @@ pub const ROOK_MAGIC: [u64; NUM_SQUARES as usize] = [
     0x2008004021040082,
 ];
 
-pub const BISHOP_MAGIC: [u64; NUM_SQUARES as usize] = [
+pub const BISHOP_MAGIC: [u64; NUM_SQUARES] = [
     0x710823004208010,
     0x11012124008030,
     0x2810808200485800,
@@ -162,14 +162,14 @@ fn empty_board_index(piece: Piece, sq: u8) -> usize {
 fn rook_mask(sq: u8) -> u64 {
     let mut mask: u64 = 0;
     for rank in (Rank2 as u8)..(Rank8 as u8) {
-        if rank == rank_of(sq) as u8 {
+        if rank == rank_of(sq) {
             continue;
         }
         let file = file_of(sq);
         mask |= RANK_BITBOARDS[rank as usize] & FILE_BITBOARDS[file as usize];
     }
     for file in (FileB as u8)..(FileH as u8) {
-        if file == file_of(sq) as u8 {
+        if file == file_of(sq) {
             continue;
         }
         let rank = rank_of(sq);
@@ -386,7 +386,7 @@ fn init_rook() {
         for _ in 0..n {
             let attack = gen_rook_attack(sq, variation);
             let idx = table_index + (((mask & variation) * magic) >> shift) as usize;
-            unsafe { ROOK_ATTACK_TABLE[idx as usize] = attack };
+            unsafe { ROOK_ATTACK_TABLE[idx] = attack };
             variation = (variation - mask) & mask;
         }
         table_index += n as usize;
@@ -410,7 +410,7 @@ fn init_bishop() {
         for _ in 0..n {
             let attack = gen_bishop_attack(sq, variation);
             let idx = table_index + (((mask & variation) * magic) >> shift) as usize;
-            unsafe { BISHOP_ATTACK_TABLE[idx as usize] = attack };
+            unsafe { BISHOP_ATTACK_TABLE[idx] = attack };
             variation = (variation - mask) & mask;
         }
         table_index += n as usize;
@@ -531,11 +531,11 @@ pub fn queen_attacks(queens: u64, occupancy: u64) -> u64 {
 }
 
 pub fn king_attacks(king: u64) -> u64 {
-    let sq = bb_lsb(king) as u8;
+    let sq = bb_lsb(king);
     empty_board_attack(King, sq)
 }
 
-pub fn all_attacks(position: Position, attacker: Color) -> u64 {
+pub fn all_attacks(position: &Position, attacker: Color) -> u64 {
     let color = attacker;
     let occupancy = position.occupancy();
     let mut attacks: u64 = 0;
@@ -545,7 +545,9 @@ pub fn all_attacks(position: Position, attacker: Color) -> u64 {
     attacks |= bishop_attacks(position.bishops(color), occupancy);
     attacks |= rook_attacks(position.rooks(color), occupancy);
     attacks |= queen_attacks(position.queens(color), occupancy);
-    attacks |= king_attacks(position.king(color));
+    if position.king(color) != 0 {
+        attacks |= king_attacks(position.king(color));
+    }
 
     attacks
 }
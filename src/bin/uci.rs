@@ -0,0 +1,7 @@
+use rust_experiments::*;
+
+fn main() {
+    attack::init();
+    bitboard::init();
+    uci::run();
+}
@@ -2,11 +2,11 @@ use crate::attack::*;
 use crate::square::*;
 use crate::things::*;
 
-static mut BB_BTWN: &'static mut [u64] = &mut [0; 64 * 64];
-static mut BB_RAYS: &'static mut [u64] = &mut [0; 64 * 64];
+static mut BB_BTWN: &mut [u64] = &mut [0; 64 * 64];
+static mut BB_RAYS: &mut [u64] = &mut [0; 64 * 64];
 
 pub const RANK_BITBOARDS: [u64; 8] = [
-    0xff << 0,
+    0xff,
     0xff << 8,
     0xff << 16,
     0xff << 24,
@@ -16,7 +16,7 @@ pub const RANK_BITBOARDS: [u64; 8] = [
     0xff << 56,
 ];
 pub const FILE_BITBOARDS: [u64; 8] = [
-    0x101010101010101 << 0,
+    0x101010101010101,
     0x101010101010101 << 1,
     0x101010101010101 << 2,
     0x101010101010101 << 3,
@@ -80,10 +80,14 @@ pub fn bb_lsb(bb: u64) -> u8 {
     bb.trailing_zeros() as u8
 }
 
+pub fn bb_msb(bb: u64) -> u8 {
+    63 - bb.leading_zeros() as u8
+}
+
 pub fn bb_pop(bb: &mut u64) -> u8 {
     let r = bb_lsb(*bb);
     *bb = *bb & (*bb - 1);
-    r as u8
+    r
 }
 
 pub fn bb_north(bb: u64) -> u64 {
@@ -129,17 +133,17 @@ pub fn bb_debug(bb: u64) {
     s.push_str("   +---+---+---+---+---+---+---+---+\n");
 
     for rank in (0..8).rev() {
-        s.push_str(" ");
+        s.push(' ');
         s.push_str(&(rank + 1).to_string());
-        s.push_str(" ");
-        for file in 0..8 {
+        s.push(' ');
+        for file_bb in FILE_BITBOARDS {
             s.push_str("| ");
-            if (bb & (RANK_BITBOARDS[rank] & FILE_BITBOARDS[file])) != 0 {
-                s.push_str("X");
+            if (bb & (RANK_BITBOARDS[rank] & file_bb)) != 0 {
+                s.push('X');
             } else {
-                s.push_str(" ");
+                s.push(' ');
             }
-            s.push_str(" ");
+            s.push(' ');
         }
         s.push_str("|\n   +---+---+---+---+---+---+---+---+\n");
     }
@@ -148,8 +152,8 @@ pub fn bb_debug(bb: u64) {
     println!("{} {:#x}", s, bb);
 }
 
-fn white_pawn_advances(pawn: u64, occupancy: u64) -> u64 {
-    let unmoved = pawn & RANK_BITBOARDS[Rank2 as usize];
+fn white_pawn_advances(pawn: u64, occupancy: u64, unmoved_ranks: u64) -> u64 {
+    let unmoved = pawn & unmoved_ranks;
     let mut advances = bb_north(pawn);
 
     advances &= !occupancy;
@@ -157,8 +161,8 @@ fn white_pawn_advances(pawn: u64, occupancy: u64) -> u64 {
     advances
 }
 
-fn black_pawn_advances(pawn: u64, occupancy: u64) -> u64 {
-    let unmoved = pawn & RANK_BITBOARDS[Rank7 as usize];
+fn black_pawn_advances(pawn: u64, occupancy: u64, unmoved_ranks: u64) -> u64 {
+    let unmoved = pawn & unmoved_ranks;
     let mut advances = bb_south(pawn);
 
     advances &= !occupancy;
@@ -166,10 +170,24 @@ fn black_pawn_advances(pawn: u64, occupancy: u64) -> u64 {
     advances
 }
 
-pub fn pawn_advances(pawn: u64, occupancy: u64, color: Color) -> u64 {
+// Pawns can advance two squares from their second rank, and in Horde also
+// from their first.
+pub fn pawn_advances(pawn: u64, occupancy: u64, color: Color, first_rank_double_push: bool) -> u64 {
     match color {
-        White => white_pawn_advances(pawn, occupancy),
-        Black => black_pawn_advances(pawn, occupancy),
+        White => {
+            let mut unmoved_ranks = RANK_BITBOARDS[Rank2 as usize];
+            if first_rank_double_push {
+                unmoved_ranks |= RANK_BITBOARDS[Rank1 as usize];
+            }
+            white_pawn_advances(pawn, occupancy, unmoved_ranks)
+        }
+        Black => {
+            let mut unmoved_ranks = RANK_BITBOARDS[Rank7 as usize];
+            if first_rank_double_push {
+                unmoved_ranks |= RANK_BITBOARDS[Rank8 as usize];
+            }
+            black_pawn_advances(pawn, occupancy, unmoved_ranks)
+        }
         _ => panic!("bogus color"),
     }
 }
@@ -0,0 +1,172 @@
+use crate::bitboard::*;
+use crate::moves::*;
+use crate::position::Position;
+use crate::things::*;
+use crate::variant::*;
+
+const POCKET_ORDER: [Piece; 5] = [Queen, Rook, Bishop, Knight, Pawn];
+
+// Crazyhouse: captured pieces change color and go into the capturer's pocket,
+// and instead of moving, a player may drop a piece from their pocket onto any
+// empty square.  A captured piece that was promoted goes back to being a pawn.
+#[derive(Clone, Copy)]
+pub struct Crazyhouse;
+
+// Bughouse is crazyhouse on two boards, where captured pieces go to the
+// capturer's partner on the other board instead.  See BughouseGame.
+#[derive(Clone, Copy)]
+pub struct Bughouse;
+
+// The piece a move's capture puts in hand, if any.
+fn captured_in_hand(undo: &Undo) -> Option<Piece> {
+    let dst = mv_get_dst(undo.mv);
+    match mv_get_kind(undo.mv) {
+        MOVE_ENPASSANT => Some(Pawn),
+        MOVE_DROP | MOVE_CASTLE => None,
+        _ if undo.captured == NoPiece => None,
+        _ if undo.promoted & bb_from_sq(dst) != 0 => Some(Pawn),
+        _ => Some(undo.captured),
+    }
+}
+
+// Promoted pieces are remembered so they can be demoted when captured.  The
+// bitboard follows each promoted piece as it moves.
+fn update_promoted(pos: &mut Position, undo: &Undo) {
+    let src_bb = bb_from_sq(mv_get_src(undo.mv));
+    let dst_bb = bb_from_sq(mv_get_dst(undo.mv));
+    match mv_get_kind(undo.mv) {
+        MOVE_NORMAL => {
+            pos.promoted &= !(src_bb | dst_bb);
+            if undo.promoted & src_bb != 0 {
+                pos.promoted |= dst_bb;
+            }
+        }
+        MOVE_PROMO => pos.promoted |= dst_bb,
+        _ => {}
+    }
+}
+
+fn parse_pocket(pos: &mut Position, ext: &FenExtensions) {
+    if let Some(pocket) = ext.pocket {
+        for c in pocket.chars().filter(|c| *c != '-') {
+            pos.add_to_pocket(color_from_char(c), piece_from_char(c));
+        }
+    }
+}
+
+fn pocket_to_str(pos: &Position) -> String {
+    let mut s = String::from("[");
+    for color in [White, Black] {
+        for piece in POCKET_ORDER {
+            for _ in 0..pos.pocket_count(color, piece) {
+                s.push_str(&piece_to_str(piece, color));
+            }
+        }
+    }
+    s.push(']');
+    s
+}
+
+impl Variant for Crazyhouse {
+    const DROPS: bool = true;
+
+    fn after_move(pos: &mut Position, undo: &mut Undo) {
+        update_promoted(pos, undo);
+        if let Some(piece) = captured_in_hand(undo) {
+            // The turn has passed, so the capturer is now the enemy.
+            pos.add_to_pocket(pos.enemy(), piece);
+        }
+    }
+
+    fn before_unmake(pos: &mut Position, undo: &Undo) {
+        if let Some(piece) = captured_in_hand(undo) {
+            pos.remove_from_pocket(pos.enemy(), piece);
+        }
+        pos.promoted = undo.promoted;
+    }
+
+    fn parse_fen_extensions(pos: &mut Position, ext: &FenExtensions) {
+        parse_pocket(pos, ext);
+    }
+
+    fn fen_board_suffix(pos: &Position) -> String {
+        pocket_to_str(pos)
+    }
+}
+
+impl Variant for Bughouse {
+    const DROPS: bool = true;
+
+    fn after_move(pos: &mut Position, undo: &mut Undo) {
+        update_promoted(pos, undo);
+    }
+
+    fn before_unmake(pos: &mut Position, undo: &Undo) {
+        pos.promoted = undo.promoted;
+    }
+
+    fn parse_fen_extensions(pos: &mut Position, ext: &FenExtensions) {
+        parse_pocket(pos, ext);
+    }
+
+    fn fen_board_suffix(pos: &Position) -> String {
+        pocket_to_str(pos)
+    }
+}
+
+// A bughouse game: two teams of two, each team playing white on one board and
+// black on the other.  A piece captured on one board keeps its color and goes
+// to the capturer's partner, who plays that color on the other board.
+pub struct BughouseGame {
+    pub boards: [Position; 2],
+    history: Vec<(usize, Undo)>,
+}
+
+impl BughouseGame {
+    pub fn new(fen_a: &str, fen_b: &str) -> BughouseGame {
+        let mut boards = [Position::new(), Position::new()];
+        boards[0].from_variant_fen::<Bughouse>(fen_a);
+        boards[1].from_variant_fen::<Bughouse>(fen_b);
+        BughouseGame {
+            boards,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn legal_moves(&self, board: usize) -> MoveList {
+        let mut move_generator = MoveGen::<Bughouse>::for_variant(&self.boards[board]);
+        move_generator.gen_legal_moves();
+        move_generator.moves
+    }
+
+    pub fn make_move(&mut self, board: usize, mv: u16) {
+        let undo = self.boards[board].make_variant_move::<Bughouse>(mv);
+        if let Some(piece) = captured_in_hand(&undo) {
+            // The captured piece's color is the side now to move.
+            let color = self.boards[board].side;
+            self.boards[1 - board].add_to_pocket(color, piece);
+        }
+        self.history.push((board, undo));
+    }
+
+    // Take back the last move made on either board.
+    pub fn unmake_move(&mut self) {
+        let (board, undo) = self.history.pop().expect("no moves to unmake");
+        if let Some(piece) = captured_in_hand(&undo) {
+            let color = self.boards[board].side;
+            self.boards[1 - board].remove_from_pocket(color, piece);
+        }
+        self.boards[board].unmake_variant_move::<Bughouse>(undo);
+    }
+
+    // The game is over as soon as either board is; returns that board and its
+    // outcome.  The team of the winning color on that board wins.
+    pub fn outcome(&self) -> Option<(usize, Outcome)> {
+        for board in 0..2 {
+            if let Some(outcome) = self.boards[board].outcome::<Bughouse>() {
+                return Some((board, outcome));
+            }
+        }
+        None
+    }
+}
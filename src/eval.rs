@@ -0,0 +1,418 @@
+use crate::attack::*;
+use crate::bitboard::*;
+use crate::pawns::*;
+use crate::position::Position;
+use crate::square::*;
+use crate::things::*;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+// A middlegame and an endgame score, which evaluate() blends according to
+// how much material is left on the board.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Score {
+        Score { mg, eg }
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+// Material and piece-square values are the PeSTO tables.
+pub const MG_VALUES: [i32; NUM_PIECES] = [82, 337, 365, 477, 1025, 0];
+pub const EG_VALUES: [i32; NUM_PIECES] = [94, 281, 297, 512, 936, 0];
+
+// Each piece left on the board moves the game phase towards the middlegame.
+// With all of them there the phase is MAX_PHASE; promotions can push it
+// higher, in which case it's capped.
+pub const PHASE_VALUES: [i32; NUM_PIECES] = [0, 1, 1, 2, 4, 0];
+pub const MAX_PHASE: i32 = 24;
+
+// Piece-square tables from white's point of view, laid out as the board is
+// drawn: A8 at the top left, H1 at the bottom right.
+#[rustfmt::skip]
+const MG_PST: [[i32; NUM_SQUARES]; NUM_PIECES] = [
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         98, 134,  61,  95,  68, 126,  34, -11,
+         -6,   7,  26,  31,  65,  56,  25, -20,
+        -14,  13,   6,  21,  23,  12,  17, -23,
+        -27,  -2,  -5,  12,  17,   6,  10, -25,
+        -26,  -4,  -4, -10,   3,   3,  33, -12,
+        -35,  -1, -20, -23, -15,  24,  38, -22,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    [
+       -167, -89, -34, -49,  61, -97, -15,-107,
+        -73, -41,  72,  36,  23,  62,   7, -17,
+        -47,  60,  37,  65,  84, 129,  73,  44,
+         -9,  17,  19,  53,  37,  69,  18,  22,
+        -13,   4,  16,  13,  28,  19,  21,  -8,
+        -23,  -9,  12,  10,  19,  17,  25, -16,
+        -29, -53, -12,  -3,  -1,  18, -14, -19,
+       -105, -21, -58, -33, -17, -28, -19, -23,
+    ],
+    [
+        -29,   4, -82, -37, -25, -42,   7,  -8,
+        -26,  16, -18, -13,  30,  59,  18, -47,
+        -16,  37,  43,  40,  35,  50,  37,  -2,
+         -4,   5,  19,  50,  37,  37,   7,  -2,
+         -6,  13,  13,  26,  34,  12,  10,   4,
+          0,  15,  15,  15,  14,  27,  18,  10,
+          4,  15,  16,   0,   7,  21,  33,   1,
+        -33,  -3, -14, -21, -13, -12, -39, -21,
+    ],
+    [
+         32,  42,  32,  51,  63,   9,  31,  43,
+         27,  32,  58,  62,  80,  67,  26,  44,
+         -5,  19,  26,  36,  17,  45,  61,  16,
+        -24, -11,   7,  26,  24,  35,  -8, -20,
+        -36, -26, -12,  -1,   9,  -7,   6, -23,
+        -45, -25, -16, -17,   3,   0,  -5, -33,
+        -44, -16, -20,  -9,  -1,  11,  -6, -71,
+        -19, -13,   1,  17,  16,   7, -37, -26,
+    ],
+    [
+        -28,   0,  29,  12,  59,  44,  43,  45,
+        -24, -39,  -5,   1, -16,  57,  28,  54,
+        -13, -17,   7,   8,  29,  56,  47,  57,
+        -27, -27, -16, -16,  -1,  17,  -2,   1,
+         -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+        -14,   2, -11,  -2,  -5,   2,  14,   5,
+        -35,  -8,  11,   2,   8,  15,  -3,   1,
+         -1, -18,  -9,  10, -15, -25, -31, -50,
+    ],
+    [
+        -65,  23,  16, -15, -56, -34,   2,  13,
+         29,  -1, -20,  -7,  -8,  -4, -38, -29,
+         -9,  24,   2, -16, -20,   6,  22, -22,
+        -17, -20, -12, -27, -30, -25, -14, -36,
+        -49,  -1, -27, -39, -46, -44, -33, -51,
+        -14, -14, -22, -46, -44, -30, -15, -27,
+          1,   7,  -8, -64, -43, -16,   9,   8,
+        -15,  36,  12, -54,   8, -28,  24,  14,
+    ],
+];
+
+#[rustfmt::skip]
+const EG_PST: [[i32; NUM_SQUARES]; NUM_PIECES] = [
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+        178, 173, 158, 134, 147, 132, 165, 187,
+         94, 100,  85,  67,  56,  53,  82,  84,
+         32,  24,  13,   5,  -2,   4,  17,  17,
+         13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+          4,   7,  -6,   1,   0,  -5,  -1,  -8,
+         13,   8,   8,  10,  13,   0,   2,  -7,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    [
+        -58, -38, -13, -28, -31, -27, -63, -99,
+        -25,  -8, -25,  -2,  -9, -25, -24, -52,
+        -24, -20,  10,   9,  -1,  -9, -19, -41,
+        -17,   3,  22,  22,  22,  11,   8, -18,
+        -18,  -6,  16,  25,  16,  17,   4, -18,
+        -23,  -3,  -1,  15,  10,  -3, -20, -22,
+        -42, -20, -10,  -5,  -2, -20, -23, -44,
+        -29, -51, -23, -15, -22, -18, -50, -64,
+    ],
+    [
+        -14, -21, -11,  -8,  -7,  -9, -17, -24,
+         -8,  -4,   7, -12,  -3, -13,  -4, -14,
+          2,  -8,   0,  -1,  -2,   6,   0,   4,
+         -3,   9,  12,   9,  14,  10,   3,   2,
+         -6,   3,  13,  19,   7,  10,  -3,  -9,
+        -12,  -3,   8,  10,  13,   3,  -7, -15,
+        -14, -18,  -7,  -1,   4,  -9, -15, -27,
+        -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+    ],
+    [
+         13,  10,  18,  15,  12,  12,   8,   5,
+         11,  13,  13,  11,  -3,   3,   8,   3,
+          7,   7,   7,   5,   4,  -3,  -5,  -3,
+          4,   3,  13,   1,   2,   1,  -1,   2,
+          3,   5,   8,   4,  -5,  -6,  -8, -11,
+         -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+         -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+         -9,   2,   3,  -1,  -5, -13,   4, -20,
+    ],
+    [
+         -9,  22,  22,  27,  27,  19,  10,  20,
+        -17,  20,  32,  41,  58,  25,  30,   0,
+        -20,   6,   9,  49,  47,  35,  19,   9,
+          3,  22,  24,  45,  57,  40,  57,  36,
+        -18,  28,  19,  47,  31,  34,  39,  23,
+        -16, -27,  15,   6,   9,  17,  10,   5,
+        -22, -23, -30, -16, -16, -23, -36, -32,
+        -33, -28, -22, -43,  -5, -32, -20, -41,
+    ],
+    [
+        -74, -35, -18, -18, -11,  15,   4, -17,
+        -12,  17,  14,  17,  17,  38,  23,  11,
+         10,  17,  23,  15,  20,  45,  44,  13,
+         -8,  22,  24,  27,  26,  33,  26,   3,
+        -18,  -4,  21,  24,  27,  23,   9, -11,
+        -19,  -3,  11,  21,  23,  16,   7,  -9,
+        -27, -11,   4,  13,  14,   4,  -5, -17,
+        -53, -34, -21, -11, -28, -14, -24, -43,
+    ],
+];
+
+// Material plus piece-square value for every piece on every square, from
+// white's point of view, so that black's pieces count negative.
+const PSQ: [[[Score; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS] = build_psq();
+
+const fn build_psq() -> [[[Score; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS] {
+    let mut psq = [[[Score::new(0, 0); NUM_SQUARES]; NUM_PIECES]; NUM_COLORS];
+    let mut piece = 0;
+    while piece < NUM_PIECES {
+        let mut sq = 0;
+        while sq < NUM_SQUARES {
+            // The tables start at A8, so flip white's squares vertically.
+            let white = sq ^ 56;
+            psq[White as usize][piece][sq] = Score::new(
+                MG_VALUES[piece] + MG_PST[piece][white],
+                EG_VALUES[piece] + EG_PST[piece][white],
+            );
+            psq[Black as usize][piece][sq] = Score::new(
+                -(MG_VALUES[piece] + MG_PST[piece][sq]),
+                -(EG_VALUES[piece] + EG_PST[piece][sq]),
+            );
+            sq += 1;
+        }
+        piece += 1;
+    }
+    psq
+}
+
+pub fn psq_score(piece: Piece, color: Color, sq: u8) -> Score {
+    PSQ[color as usize][piece as usize][sq as usize]
+}
+
+// Mobility is scored per safe square a piece attacks, relative to a typical
+// number of them, so that a piece with average mobility scores nothing.
+const MOBILITY_WEIGHTS: [Score; NUM_PIECES] = [
+    Score::new(0, 0),
+    Score::new(4, 4),
+    Score::new(5, 5),
+    Score::new(2, 4),
+    Score::new(1, 2),
+    Score::new(0, 0),
+];
+const MOBILITY_BASE: [i32; NUM_PIECES] = [0, 4, 6, 7, 13, 0];
+
+// Squares attacked by a piece, seen through the current occupancy.
+fn piece_attacks(pos: &Position, piece: Piece, sq: u8) -> u64 {
+    let bb = bb_from_sq(sq);
+    match piece {
+        Knight => knight_attacks_from(sq),
+        Bishop => bishop_attacks(bb, pos.occupancy()),
+        Rook => rook_attacks(bb, pos.occupancy()),
+        Queen => queen_attacks(bb, pos.occupancy()),
+        _ => panic!("bogus piece"),
+    }
+}
+
+// Mobility of color's pieces, counting the squares they attack which aren't
+// occupied by their own side or attacked by enemy pawns.
+pub fn mobility(pos: &Position, color: Color) -> Score {
+    let them = opposite(color);
+    let safe = !pos.color_pieces(color) & !pawn_attacks(pos.pawns(them), them);
+    let mut score = Score::default();
+    for piece in [Knight, Bishop, Rook, Queen] {
+        let weight = MOBILITY_WEIGHTS[piece as usize];
+        let mut pieces = pos.pieces(piece, color);
+        while pieces != 0 {
+            let sq = bb_pop(&mut pieces);
+            let count = bb_popcnt(piece_attacks(pos, piece, sq) & safe) as i32;
+            let moves = count - MOBILITY_BASE[piece as usize];
+            score += Score::new(weight.mg * moves, weight.eg * moves);
+        }
+    }
+    score
+}
+
+// Weight of each enemy piece's attack on a king zone square, and how much of
+// the total counts depending on the number of pieces joining in.  A lone
+// attacker isn't much of a threat.
+const KING_ATTACK_WEIGHTS: [i32; NUM_PIECES] = [0, 10, 10, 20, 40, 0];
+const KING_ATTACKER_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+// Pawns one and two squares in front of the king, on its file and the files
+// either side, and enemy pawns storming those files, by distance.
+const SHIELD_BONUS: [i32; 3] = [0, 12, 6];
+const STORM_PENALTY: [i32; 5] = [0, 8, 20, 12, 6];
+const OPEN_FILE_PENALTY: i32 = 20;
+const HALF_OPEN_FILE_PENALTY: i32 = 12;
+
+// Squares around the king, plus a ring further forward.
+pub fn king_zone(king_sq: u8, color: Color) -> u64 {
+    let around = king_attacks_from(king_sq) | bb_from_sq(king_sq);
+    around | forward(around, color)
+}
+
+// Safety of color's king: enemy pieces attacking its zone, its pawn shield,
+// enemy pawns storming it and open files next to it.  This is mostly a
+// middlegame concern, when there's still enough material for an attack.
+pub fn king_safety(pos: &Position, color: Color) -> Score {
+    let king = pos.king(color);
+    if king == 0 {
+        return Score::default();
+    }
+    let them = opposite(color);
+    let king_sq = bb_lsb(king);
+    let zone = king_zone(king_sq, color);
+    let mut mg = 0;
+
+    let mut attackers = 0;
+    let mut weight = 0;
+    for piece in [Knight, Bishop, Rook, Queen] {
+        let mut pieces = pos.pieces(piece, them);
+        while pieces != 0 {
+            let sq = bb_pop(&mut pieces);
+            let hits = bb_popcnt(piece_attacks(pos, piece, sq) & zone) as i32;
+            if hits > 0 {
+                attackers += 1;
+                weight += KING_ATTACK_WEIGHTS[piece as usize] * hits;
+            }
+        }
+    }
+    mg -= weight * KING_ATTACKER_SCALE[attackers.min(7)] / 100;
+
+    let files = file_fill(king | bb_east(king) | bb_west(king));
+    let ours = pos.pawns(color) & files;
+    let theirs = pos.pawns(them) & files;
+    let king_rank = rank_of(king_sq) as i32;
+    let mut shield = ours & front_span(king | bb_east(king) | bb_west(king), color);
+    while shield != 0 {
+        let distance = (rank_of(bb_pop(&mut shield)) as i32 - king_rank).unsigned_abs() as usize;
+        if distance < SHIELD_BONUS.len() {
+            mg += SHIELD_BONUS[distance];
+        }
+    }
+    let mut storm = theirs & front_span(RANK_BITBOARDS[king_rank as usize], color);
+    while storm != 0 {
+        let distance = (rank_of(bb_pop(&mut storm)) as i32 - king_rank).unsigned_abs() as usize;
+        if distance < STORM_PENALTY.len() {
+            mg -= STORM_PENALTY[distance];
+        }
+    }
+
+    let our_files = file_fill(pos.pawns(color));
+    let their_files = file_fill(pos.pawns(them));
+    for file_bb in FILE_BITBOARDS {
+        let file_bb = file_bb & files;
+        if file_bb == 0 || file_bb & our_files != 0 {
+            continue;
+        }
+        mg -= if file_bb & their_files == 0 {
+            OPEN_FILE_PENALTY
+        } else {
+            HALF_OPEN_FILE_PENALTY
+        };
+    }
+
+    Score::new(mg, 0)
+}
+
+// Pawn structure terms, indexed by the pawn's rank from its own side.
+const PASSED_BONUS: [Score; NUM_RANKS] = [
+    Score::new(0, 0),
+    Score::new(5, 10),
+    Score::new(5, 15),
+    Score::new(10, 25),
+    Score::new(20, 45),
+    Score::new(35, 75),
+    Score::new(60, 120),
+    Score::new(0, 0),
+];
+const CANDIDATE_BONUS: Score = Score::new(5, 12);
+const CONNECTED_BONUS: Score = Score::new(5, 5);
+const ISOLATED_PENALTY: Score = Score::new(-8, -12);
+const DOUBLED_PENALTY: Score = Score::new(-10, -20);
+const BACKWARD_PENALTY: Score = Score::new(-6, -10);
+
+fn count_score(bb: u64, score: Score) -> Score {
+    let count = bb_popcnt(bb) as i32;
+    Score::new(score.mg * count, score.eg * count)
+}
+
+// Score for color's pawn structure, from the features PawnStructure found.
+pub fn pawn_structure(pawns: &PawnStructure, color: Color) -> Score {
+    let c = color as usize;
+    let mut score = Score::default();
+    let mut passed = pawns.passed[c];
+    while passed != 0 {
+        let rank = rank_of(bb_pop(&mut passed));
+        let relative = if color == White { rank } else { 7 - rank };
+        score += PASSED_BONUS[relative as usize];
+    }
+    score += count_score(pawns.candidate[c], CANDIDATE_BONUS);
+    score += count_score(pawns.connected[c], CONNECTED_BONUS);
+    score += count_score(pawns.isolated[c], ISOLATED_PENALTY);
+    score += count_score(pawns.doubled[c], DOUBLED_PENALTY);
+    score += count_score(pawns.backward[c], BACKWARD_PENALTY);
+    score
+}
+
+// Blend a score's middlegame and endgame values by the game phase.
+pub fn taper(score: Score, phase: i32) -> i32 {
+    let phase = phase.min(MAX_PHASE);
+    (score.mg * phase + score.eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+impl Position {
+    // Static evaluation in centipawns, from the side to move's point of view.
+    pub fn evaluate(&self) -> i32 {
+        self.evaluate_with_pawns(&PawnStructure::new(self))
+    }
+
+    // The same, given the pawn structure, e.g. from a PawnTable.
+    pub fn evaluate_with_pawns(&self, pawns: &PawnStructure) -> i32 {
+        let mut score = self.psq();
+        score += pawn_structure(pawns, White) - pawn_structure(pawns, Black);
+        score += mobility(self, White) - mobility(self, Black);
+        score += king_safety(self, White) - king_safety(self, Black);
+        let score = taper(score, self.phase());
+        if self.side == White {
+            score
+        } else {
+            -score
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::moves::*;
+use crate::position::Position;
+use crate::square::*;
+use crate::things::*;
+use crate::variant::*;
+
+// Horde: white has 36 pawns and no king against black's usual army.  White
+// wins by checkmating black, and black by capturing every piece white has.
+// White's pawns on the first rank can advance two squares, though that
+// doesn't allow an en passant capture.
+#[derive(Clone, Copy)]
+pub struct Horde;
+
+impl Variant for Horde {
+    const FIRST_RANK_DOUBLE_PUSH: bool = true;
+
+    fn after_move(pos: &mut Position, undo: &mut Undo) {
+        let src = mv_get_src(undo.mv);
+        if pos.ep != NO_SQUARE && (rank_of(src) == Rank1 as u8 || rank_of(src) == Rank8 as u8) {
+            pos.ep = NO_SQUARE;
+        }
+    }
+
+    fn variant_outcome(pos: &Position) -> Option<Outcome> {
+        for color in [White, Black] {
+            if pos.color_pieces(color) == 0 {
+                return Some(Outcome::Win(opposite(color)));
+            }
+        }
+        None
+    }
+}
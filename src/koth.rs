@@ -0,0 +1,25 @@
+use crate::position::Position;
+use crate::square::*;
+use crate::things::*;
+use crate::variant::*;
+
+const CENTRE: u64 = (1 << Squares::D4 as u8)
+    | (1 << Squares::E4 as u8)
+    | (1 << Squares::D5 as u8)
+    | (1 << Squares::E5 as u8);
+
+// King of the Hill: standard chess, except that getting your king to one of
+// the four centre squares also wins.
+#[derive(Clone, Copy)]
+pub struct KingOfTheHill;
+
+impl Variant for KingOfTheHill {
+    fn variant_outcome(pos: &Position) -> Option<Outcome> {
+        for color in [White, Black] {
+            if pos.king(color) & CENTRE != 0 {
+                return Some(Outcome::Win(color));
+            }
+        }
+        None
+    }
+}
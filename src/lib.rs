@@ -0,0 +1,26 @@
+pub mod antichess;
+pub mod atomic;
+pub mod attack;
+pub mod bitboard;
+pub mod crazyhouse;
+pub mod eval;
+pub mod horde;
+pub mod koth;
+pub mod movepick;
+pub mod moves;
+pub mod pawns;
+pub mod perft;
+pub mod position;
+pub mod racingkings;
+pub mod search;
+pub mod see;
+pub mod square;
+pub mod threecheck;
+pub mod things;
+pub mod timeman;
+pub mod trace;
+pub mod tt;
+pub mod uci;
+pub mod variant;
+pub mod xboard;
+pub mod zobrist;
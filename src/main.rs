@@ -1,17 +1,35 @@
-pub mod attack;
-pub mod bitboard;
-pub mod moves;
-pub mod position;
-pub mod square;
-pub mod things;
-
-use crate::moves::*;
-use crate::square::*;
-use position::Position;
-use std::time::Instant;
-
-fn perft(depth: u32, pos: &mut Position) -> usize {
-    let mut move_generator = MoveGen::new(*pos);
+use rust_experiments::*;
+use rust_experiments::moves::*;
+use antichess::*;
+use atomic::*;
+use bitboard::*;
+use crazyhouse::*;
+use eval::*;
+use horde::*;
+use koth::*;
+use movepick::*;
+use pawns::*;
+use perft::*;
+use position::{IllegalFen, Position};
+use square::Squares;
+use square::Squares::*;
+use racingkings::*;
+use search::*;
+use tt::*;
+use things::*;
+use threecheck::*;
+use timeman::*;
+use uci::{Output, Uci};
+use xboard::XBoard;
+use variant::*;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Same as perft(), but each child is a fresh copy of the position rather than
+// the result of make_move()/unmake_move() on a shared one.
+fn perft_copy_make(depth: u32, pos: &Position) -> usize {
+    let mut move_generator = MoveGen::new(pos);
     move_generator.gen_legal_moves();
 
     if depth == 1 {
@@ -19,46 +37,911 @@ fn perft(depth: u32, pos: &mut Position) -> usize {
     }
 
     let mut nodes: usize = 0;
+    for mv in move_generator.moves {
+        nodes += perft_copy_make(depth - 1, &pos.play(mv));
+    }
+    nodes
+}
+
+// Compare make/unmake against copy-make over the same perft trees.
+fn bench(fens: &[(&str, u32)]) {
+    for (fen, depth) in fens {
+        let mut pos = Position::new();
+        pos.from_fen(fen);
+        println!("{} depth=={}", fen, depth);
+
+        let start = Instant::now();
+        let nodes = perft::<Standard>(*depth, &mut pos);
+        let usec = 1 + start.elapsed().as_micros();
+        println!(
+            "  make/unmake: {} nodes in {} ms; {} knps",
+            nodes,
+            usec / 1000,
+            nodes as u128 * 1000 / usec
+        );
+
+        let start = Instant::now();
+        let copy_nodes = perft_copy_make(*depth, &pos);
+        let usec = 1 + start.elapsed().as_micros();
+        println!(
+            "  copy-make:   {} nodes in {} ms; {} knps",
+            copy_nodes,
+            usec / 1000,
+            copy_nodes as u128 * 1000 / usec
+        );
+
+        assert!(nodes == copy_nodes);
+    }
+}
+
+// Checks that mv scores exactly expected, by see() and by see_ge() either
+// side of it.
+fn check_see(fen: &str, mv: &str, expected: i32) {
+    let mut pos = Position::new();
+    pos.from_fen(fen);
+    let mv = pos.parse_move(mv).unwrap();
+    assert!(pos.see(mv) == expected);
+    assert!(pos.see_ge(mv, expected) && !pos.see_ge(mv, expected + 1));
+}
+
+fn see_suite() {
+    // A free pawn, and one defended by a pawn.
+    check_see("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5", 100);
+    check_see("4k3/8/2p5/3p4/8/4N3/8/4K3 w - - 0 1", "e3d5", -200);
+
+    // En passant takes a pawn that isn't on the destination square, and
+    // taking it off the board uncovers the rook behind it.
+    check_see("3rk3/8/8/3pP3/8/8/8/3RK3 w - d6 0 1", "e5d6", 100);
+
+    // Promoting with a capture gains the promotion as well as the rook, and
+    // the queen is what's recaptured.
+    check_see("1r2k3/P2n4/8/8/8/8/8/4K3 w - - 0 1", "a7b8q", 400);
+    check_see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q", 1300);
+
+    // The queen behind the rook recaptures through it; without her the rook
+    // is lost for a pawn.
+    check_see("3rk3/8/8/3p4/8/8/3R4/3QK3 w - - 0 1", "d2d5", 100);
+    check_see("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", "d2d5", -400);
+
+    // The king can only recapture a square that's no longer defended, which
+    // here it is by the queen through the rook.
+    check_see("4k3/3p4/8/8/8/8/3R4/4K3 w - - 0 1", "d2d7", -400);
+    check_see("4k3/3p4/8/8/8/8/3R4/3QK3 w - - 0 1", "d2d7", 100);
+
+    // Pins are ignored, so the knight pinned to its king still recaptures.
+    check_see("8/8/R4n1k/3p4/8/2N5/8/4K3 w - - 0 1", "c3d5", -200);
+}
+
+// The material and phase that make_move()/unmake_move() keep up to date,
+// recounted from the board.
+fn recount(pos: &Position) -> (Score, i32) {
+    let mut psq = Score::default();
+    let mut phase = 0;
+    for sq in 0..64 {
+        let piece = pos.piece_on(sq);
+        if piece != NoPiece {
+            psq += psq_score(piece, pos.color_on(sq), sq);
+            phase += PHASE_VALUES[piece as usize];
+        }
+    }
+    (psq, phase)
+}
+
+// Walks a perft tree checking the incremental scores against a recount after
+// every make and unmake, and returns the number of leaves.
+fn check_incremental(depth: u32, pos: &mut Position) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+
+    let mut move_generator = MoveGen::new(pos);
+    move_generator.gen_legal_moves();
+
+    let mut nodes = 0;
     for mv in move_generator.moves {
         let undo = pos.make_move(mv);
-        nodes += perft(depth - 1, pos);
+        assert!((pos.psq(), pos.phase()) == recount(pos));
+        nodes += check_incremental(depth - 1, pos);
         pos.unmake_move(undo);
+        assert!((pos.psq(), pos.phase()) == recount(pos));
     }
     nodes
 }
 
-fn divide(depth: u32, pos: &mut Position) -> usize {
-    let mut total_nodes: usize = 0;
-    let mut move_generator = MoveGen::new(*pos);
+fn incremental_suite() {
+    // Castling both ways, en passant, promotions with and without captures,
+    // and Chess960 castling where the king and rook may land on each other.
+    let suite: [(&str, u32); 6] = [
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5),
+        ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3),
+        ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3),
+        ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", 3),
+        ("4rkr1/8/8/8/8/8/8/4RKR1 w GEge - 0 1", 3),
+    ];
+
+    let mut pos = Position::new();
+    for (fen, depth) in suite {
+        pos.from_fen(fen);
+        assert!((pos.psq(), pos.phase()) == recount(&pos));
+        let nodes = check_incremental(depth, &mut pos);
+        println!("{} depth=={}: {} nodes", fen, depth, nodes);
+        assert!(nodes == perft::<Standard>(depth, &mut pos));
+    }
+}
+
+fn bb_of<const N: usize>(squares: [Squares; N]) -> u64 {
+    squares.into_iter().fold(0, |bb, sq| bb | bb_from_sq(sq as u8))
+}
+
+fn files_of<const N: usize>(files: [File; N]) -> u64 {
+    files.into_iter().fold(0, |bb, file| bb | FILE_BITBOARDS[file as usize])
+}
+
+// Walks a perft tree checking that the pawn table agrees with a fresh
+// computation after every make and unmake.
+fn check_pawn_table(depth: u32, pos: &mut Position, table: &mut PawnTable) {
+    if depth == 0 {
+        return;
+    }
+
+    let mut move_generator = MoveGen::new(pos);
     move_generator.gen_legal_moves();
+    for mv in move_generator.moves {
+        let undo = pos.make_move(mv);
+        assert!(table.probe(pos) == PawnStructure::new(pos));
+        check_pawn_table(depth - 1, pos, table);
+        pos.unmake_move(undo);
+        assert!(table.probe(pos) == PawnStructure::new(pos));
+    }
+}
+
+fn pawns_suite() {
+    let mut pos = Position::new();
+
+    // Doubled on an otherwise empty board: the front pawn is the doubled one,
+    // and both are isolated and passed.
+    pos.from_fen("4k3/8/8/8/2P5/8/2P5/4K3 w - - 0 1");
+    assert!(PawnStructure::new(&pos) == PawnStructure {
+        passed: [bb_of([C2, C4]), 0],
+        isolated: [bb_of([C2, C4]), 0],
+        doubled: [bb_of([C4]), 0],
+        open_files: !files_of([FileC]),
+        half_open_files: [0, files_of([FileC])],
+        ..PawnStructure::default()
+    });
+
+    // d3 and e5 both have their stop square attacked, with no pawn that can
+    // come to defend it.  c4 is passed and defended by d3.
+    pos.from_fen("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1");
+    assert!(PawnStructure::new(&pos) == PawnStructure {
+        passed: [bb_of([C4]), 0],
+        isolated: [0, bb_of([E5])],
+        backward: [bb_of([D3]), bb_of([E5])],
+        connected: [bb_of([C4]), 0],
+        open_files: !files_of([FileC, FileD, FileE]),
+        half_open_files: [files_of([FileE]), files_of([FileC, FileD])],
+        ..PawnStructure::default()
+    });
+
+    // b4 has an open file, and a4 to match the one sentry on a7.
+    pos.from_fen("4k3/p7/8/8/PP6/8/8/4K3 w - - 0 1");
+    assert!(PawnStructure::new(&pos) == PawnStructure {
+        isolated: [0, bb_of([A7])],
+        connected: [bb_of([A4, B4]), 0],
+        candidate: [bb_of([B4]), 0],
+        open_files: !files_of([FileA, FileB]),
+        half_open_files: [0, files_of([FileB])],
+        ..PawnStructure::default()
+    });
+
+    // With c6 as a second sentry, b4 is no longer a candidate.  c6 is now
+    // backward: b4 attacks c5, and a7 can never defend it.
+    pos.from_fen("4k3/p7/2p5/8/PP6/8/8/4K3 w - - 0 1");
+    assert!(PawnStructure::new(&pos) == PawnStructure {
+        isolated: [0, bb_of([A7, C6])],
+        backward: [0, bb_of([C6])],
+        connected: [bb_of([A4, B4]), 0],
+        open_files: !files_of([FileA, FileB, FileC]),
+        half_open_files: [files_of([FileC]), files_of([FileB])],
+        ..PawnStructure::default()
+    });
+
+    // A table small enough that entries are replaced all the time.
+    let mut table = PawnTable::new(16);
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    ] {
+        pos.from_fen(fen);
+        assert!(table.probe(&pos) == PawnStructure::new(&pos));
+        check_pawn_table(3, &mut pos, &mut table);
+    }
+}
+
+fn king_safety_suite() {
+    // The squares around the king, and a ring in front of them.
+    assert!(king_zone(G1 as u8, White) == bb_of([F1, G1, H1, F2, G2, H2, F3, G3, H3]));
+    assert!(king_zone(G8 as u8, Black) == bb_of([F8, G8, H8, F7, G7, H7, F6, G6, H6]));
+    assert!(king_zone(A1 as u8, White) == bb_of([A1, B1, A2, B2, A3, B3]));
+
+    // A full shield is worth something, and it's worth less with a gap, less
+    // still if the file is half-open and least if it's fully open.
+    let mut pos = Position::new();
+    let mut white_safety = |fen: &str| {
+        pos.from_fen(fen);
+        king_safety(&pos, White).mg
+    };
+    let shielded = white_safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+    let gap = white_safety("6k1/5ppp/8/8/8/6P1/5P1P/6K1 w - - 0 1");
+    let half_open = white_safety("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1");
+    let open = white_safety("6k1/5p1p/8/8/8/8/5P1P/6K1 w - - 0 1");
+    assert!(shielded > gap && gap > half_open && half_open > open && open > 0);
+
+    // Enemy pawns storming the king count against it.
+    let stormed = white_safety("6k1/5p1p/8/8/6p1/8/5PPP/6K1 w - - 0 1");
+    assert!(stormed < shielded);
+
+    // A lone attacker on the king zone isn't a threat, but two are.
+    let mut pos = Position::new();
+    let mut black_safety = |fen: &str| {
+        pos.from_fen(fen);
+        king_safety(&pos, Black).mg
+    };
+    let quiet = black_safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+    let knight = black_safety("6k1/5ppp/8/6N1/8/8/5PPP/6K1 w - - 0 1");
+    let knight_and_queen = black_safety("6k1/5ppp/8/6NQ/8/8/5PPP/6K1 w - - 0 1");
+    assert!(quiet > 0 && knight == quiet && knight_and_queen < 0);
+
+    // A knight is worth more in the centre than in the corner, and less when
+    // enemy pawns cover the squares it could go to.  Kings have no mobility.
+    let mut pos = Position::new();
+    let mut white_mobility = |fen: &str| {
+        pos.from_fen(fen);
+        mobility(&pos, White).mg
+    };
+    let centre = white_mobility("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+    let corner = white_mobility("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+    let covered = white_mobility("4k3/3p4/8/8/3N4/8/8/4K3 w - - 0 1");
+    assert!(centre > 0 && corner < 0 && centre > covered && covered > corner);
+    assert!(white_mobility("4k3/8/8/8/8/8/8/4K3 w - - 0 1") == 0);
+
+    // Both are scored the same for either side: the attack on black's king
+    // is worth as much to white as its mirror image is to black.
+    pos.from_fen("6k1/5ppp/8/6NQ/8/8/5PPP/6K1 w - - 0 1");
+    let white_attacks = pos.evaluate();
+    pos.from_fen("6k1/5ppp/8/8/6nq/8/5PPP/6K1 b - - 0 1");
+    assert!(pos.evaluate() == white_attacks);
+}
+
+// The trace only reports on the evaluation, so it must add up to the same
+// thing.
+fn trace_suite() {
+    let mut pos = Position::new();
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 b - - 0 10",
+        "6k1/5ppp/8/6NQ/8/8/5PPP/6K1 w - - 0 1",
+        "4k3/p7/2p5/8/PP6/8/8/4K3 b - - 0 1",
+    ] {
+        pos.from_fen(fen);
+        let trace = pos.trace();
+        let white_pov = if pos.side == White { pos.evaluate() } else { -pos.evaluate() };
+        println!("{} score {}", fen, trace.score);
+        assert!(trace.score == white_pov && trace.phase == pos.phase());
+
+        // Material and piece-square terms split psq() between them.
+        let (w, b) = (White as usize, Black as usize);
+        let [(_, material), (_, pst)] = [trace.terms[0], trace.terms[1]];
+        assert!(material[w] - material[b] + pst[w] - pst[b] == pos.psq());
+    }
+}
+
+// Published Chess960 perft results.
+fn chess960_suite() {
+    let suite: [(&str, u32, usize); 5] = [
+        ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", 5, 8146062),
+        ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", 5, 16253601),
+        ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", 5, 6417013),
+        ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", 5, 9183776),
+        ("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", 5, 34030312),
+    ];
+
+    let mut pos = Position::new();
+    for (fen, depth, nodes) in suite {
+        pos.from_fen(fen);
+        pos.debug();
+        assert!(nodes == divide::<Standard>(depth, &mut pos));
+    }
+}
+
+// Crazyhouse perft results, as published alongside Fairy-Stockfish.
+fn crazyhouse_suite() {
+    let suite: [(&str, u32, usize); 2] = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1", 5, 4888832),
+        ("2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1", 2, 75353),
+    ];
+
+    let mut pos = Position::new();
+    for (fen, depth, nodes) in suite {
+        pos.from_variant_fen::<Crazyhouse>(fen);
+        pos.debug();
+        assert!(nodes == divide::<Crazyhouse>(depth, &mut pos));
+    }
+}
+
+fn bughouse_suite() {
+    // The null move isn't a pawn dropped on a1.
+    assert!(!mv_is_drop(NULL_MOVE) && mv_get_kind(NULL_MOVE) == MOVE_NORMAL);
+
+    let mut game = BughouseGame::new(
+        "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR[] w KQkq - 0 2",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR[] b KQkq - 0 1",
+    );
+    game.boards[1].debug();
+    assert!(game.legal_moves(1).len() == 20);
+
+    // White's exd5 on the first board hands the pawn to black on the second,
+    // who can drop it on any empty square off the back ranks.
+    game.make_move(0, mv_create_normal(E4 as u8, D5 as u8));
+    assert!(game.boards[0].to_variant_fen::<Bughouse>().starts_with("rnbqkbnr/ppp1pppp/8/3P4/8/8/PPPP1PPP/RNBQKBNR[] b"));
+    assert!(game.boards[1].pocket_count(Black, Pawn) == 1);
+    let moves = game.legal_moves(1);
+    assert!(moves.len() == 20 + 32);
+    assert!(moves.contains(&mv_create_drop(E5 as u8, Pawn)));
+    assert!(!moves.contains(&mv_create_drop(E1 as u8, Pawn)));
+
+    game.make_move(1, mv_create_drop(E5 as u8, Pawn));
+    assert!(game.boards[1].pocket_count(Black, Pawn) == 0);
+    assert!(game.boards[1].to_variant_fen::<Bughouse>().starts_with("rnbqkbnr/pppppppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR[] w"));
+    assert!(game.outcome().is_none());
+
+    // Taking both moves back returns the pawn from the second board's pocket
+    // to the first board.
+    game.unmake_move();
+    assert!(game.boards[1].pocket_count(Black, Pawn) == 1);
+    game.unmake_move();
+    assert!(game.boards[1].pocket_count(Black, Pawn) == 0);
+    assert!(game.boards[0].piece_on(D5 as u8) == Pawn && game.boards[0].color_on(D5 as u8) == Black);
+}
+
+// Atomic perft results, as used by lichess and python-chess.
+fn atomic_suite() {
+    let suite: [(&str, u32, usize); 3] = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 4, 197326),
+        ("rn1qkb1r/p5pp/2p5/3p4/N3P3/5P2/PPP4P/R1BQK3 w Qkq - 0 1", 4, 714499),
+        ("8/8/8/8/8/8/2k5/rR4KR w KQ - 0 1", 3, 4364),
+    ];
+
+    let mut pos = Position::new();
+    for (fen, depth, nodes) in suite {
+        pos.from_variant_fen::<Atomic>(fen);
+        pos.debug();
+        let fen = pos.to_variant_fen::<Atomic>();
+        assert!(nodes == divide::<Atomic>(depth, &mut pos));
+        assert!(fen == pos.to_variant_fen::<Atomic>());
+    }
+}
+
+fn antichess_suite() {
+    let mut pos = Position::new();
+    pos.from_variant_fen::<Antichess>("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1");
+    pos.debug();
+    assert!(2732672 == divide::<Antichess>(5, &mut pos));
+
+    // A pawn promotes to a second king, and both kings then move: five
+    // moves each, after any of black's three replies.
+    pos.from_variant_fen::<Antichess>("8/1P6/8/8/8/8/8/4K2k w - - 0 1");
+    assert!(perft::<Antichess>(1, &mut pos) == 10);
+    pos.make_variant_move::<Antichess>(mv_create_promo(B7 as u8, B8 as u8, King as u8));
+    assert!(pos.to_variant_fen::<Antichess>() == "1K6/8/8/8/8/8/8/4K2k b - - 0 1");
+    assert!(perft::<Antichess>(2, &mut pos) == 30);
+
+    // Only the second king can capture, so it must.
+    pos.from_variant_fen::<Antichess>("1K6/p7/8/8/8/8/8/4K2k w - - 0 1");
+    assert!(perft::<Antichess>(1, &mut pos) == 1);
+}
+
+fn three_check_suite() {
+    let mut pos = Position::new();
+    pos.from_variant_fen::<ThreeCheck>("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1");
+    pos.debug();
+    assert!(pos.to_variant_fen::<ThreeCheck>().ends_with(" 0 1 +0+0"));
+    assert!(4865609 == divide::<ThreeCheck>(5, &mut pos));
+
+    // The third check wins, and taking it back takes the check off the count.
+    let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1 +2+0";
+    pos.from_variant_fen::<ThreeCheck>(fen);
+    assert!(pos.outcome::<ThreeCheck>().is_none());
+    let undo = pos.make_variant_move::<ThreeCheck>(mv_create_normal(A1 as u8, A8 as u8));
+    assert!(pos.to_variant_fen::<ThreeCheck>() == "R3k3/8/8/8/8/8/8/4K3 b - - 1 1 +3+0");
+    assert!(pos.outcome::<ThreeCheck>() == Some(Outcome::Win(White)));
+    assert!(perft::<ThreeCheck>(1, &mut pos) == 0);
+    pos.unmake_variant_move::<ThreeCheck>(undo);
+    assert!(pos.to_variant_fen::<ThreeCheck>() == fen);
+
+    // Counters need exactly two counts, each no more than it takes to win,
+    // and a bad one leaves the position as it was.
+    let board = "4k3/8/8/8/8/8/8/R3K3 w - -";
+    for (checks, given) in [("+2+1", "+2+1"), ("+0+3", "+0+3"), ("1+2", "+2+1"), ("3+0", "+0+3")] {
+        let fen = format!("{} 0 1 {}", board, checks);
+        assert!(pos.try_from_variant_fen::<ThreeCheck>(&fen).is_ok());
+        assert!(pos.to_variant_fen::<ThreeCheck>() == format!("{} 0 1 {}", board, given));
+    }
+    for checks in ["+2", "5+5", "+4+0", "x+1", "+1+1+1", "+1+"] {
+        let fen = format!("{} 0 1 {}", board, checks);
+        assert!(pos.try_from_variant_fen::<ThreeCheck>(&fen) == Err(IllegalFen("bad check counters")));
+        assert!(pos.to_variant_fen::<ThreeCheck>() == format!("{} 0 1 +0+3", board));
+    }
+}
+
+fn koth_suite() {
+    let mut pos = Position::new();
+    pos.from_variant_fen::<KingOfTheHill>("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    pos.debug();
+    assert!(4865609 == divide::<KingOfTheHill>(5, &mut pos));
+
+    // White's king has reached the centre, so black has no moves.
+    pos.from_variant_fen::<KingOfTheHill>("rnbq1bnr/ppp2ppp/3k4/4p2Q/3PK3/8/PPP2PPP/RNB2BNR b - - 0 1");
+    assert!(pos.outcome::<KingOfTheHill>() == Some(Outcome::Win(White)));
+    assert!(perft::<KingOfTheHill>(1, &mut pos) == 0);
+}
 
-    println!("node count, depth=={}", depth);
+fn racing_kings_suite() {
+    let mut pos = Position::new();
+    pos.from_variant_fen::<RacingKings>("8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1");
+    pos.debug();
+    assert!(296242 == divide::<RacingKings>(4, &mut pos));
+
+    // White has arrived and black can't follow.
+    pos.from_variant_fen::<RacingKings>("K7/8/k7/8/8/8/8/8 b - - 0 1");
+    assert!(pos.outcome::<RacingKings>() == Some(Outcome::Win(White)));
+
+    // The bishop and knight cover f8 and h8, and the rook covers g8 once the
+    // king has stepped off the g-file.
+    pos.from_variant_fen::<RacingKings>("K7/5Nk1/3B4/8/8/8/8/6R1 b - - 0 1");
+    assert!(pos.outcome::<RacingKings>() == Some(Outcome::Win(White)));
+
+    // White has arrived, but black gets one more move to draw.
+    pos.from_variant_fen::<RacingKings>("K7/7k/8/8/8/8/8/8 b - - 0 1");
+    assert!(pos.outcome::<RacingKings>().is_none());
+    pos.make_variant_move::<RacingKings>(mv_create_normal(H7 as u8, H8 as u8));
+    assert!(pos.outcome::<RacingKings>() == Some(Outcome::Draw));
+
+    // Rg7 and Rh1 would give check, so the rook can't go there.
+    pos.from_variant_fen::<RacingKings>("8/7k/8/8/8/8/8/K5R1 w - - 0 1");
+    let mut move_generator = MoveGen::<RacingKings>::for_variant(&pos);
+    move_generator.gen_legal_moves();
+    let moves = move_generator.moves;
+    assert!(!moves.contains(&mv_create_normal(G1 as u8, G7 as u8)));
+    assert!(!moves.contains(&mv_create_normal(G1 as u8, H1 as u8)));
+    assert!(moves.contains(&mv_create_normal(G1 as u8, G6 as u8)));
+}
+
+fn horde_suite() {
+    let suite: [(&str, u32, usize); 2] = [
+        ("rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1", 5, 265223),
+        ("4k3/pp4q1/3P2p1/8/P3PP2/PPP2r2/PPP5/PPPP4 b - - 0 1", 4, 56539),
+    ];
+
+    let mut pos = Position::new();
+    for (fen, depth, nodes) in suite {
+        pos.from_variant_fen::<Horde>(fen);
+        pos.debug();
+        assert!(nodes == divide::<Horde>(depth, &mut pos));
+    }
+
+    // A pawn on the first rank can advance two squares, but can't then be
+    // taken en passant.
+    pos.from_variant_fen::<Horde>("4k3/8/8/8/8/1p6/8/P7 w - - 0 1");
+    assert!(perft::<Horde>(1, &mut pos) == 2);
+    pos.make_variant_move::<Horde>(mv_create_normal(A1 as u8, A3 as u8));
+    assert!(pos.to_variant_fen::<Horde>() == "4k3/8/8/8/8/Pp6/8/8 b - - 0 1");
+
+    // Capturing the last of the horde wins.
+    pos.from_variant_fen::<Horde>("4k3/8/8/8/8/8/3p4/4P3 b - - 0 1");
+    assert!(pos.outcome::<Horde>().is_none());
+    pos.make_variant_move::<Horde>(mv_create_promo(D2 as u8, E1 as u8, Queen as u8));
+    assert!(pos.outcome::<Horde>() == Some(Outcome::Win(Black)));
+}
+
+fn search_suite() {
+    let mut searcher = Search::new();
+    let mut pos = Position::new();
+    let mut search = |pos: &Position, history: &[u64], depth: u32| {
+        let limits = Limits {
+            depth,
+            ..Limits::default()
+        };
+        searcher.search(pos, history, limits, |info| {
+            let pv: Vec<String> = info.pv.iter().map(|&mv| mv_to_str(mv, false)).collect();
+            println!(
+                "depth {} score {} nodes {} nps {} pv {}",
+                info.depth,
+                info.score,
+                info.nodes,
+                info.nps,
+                pv.join(" ")
+            );
+        })
+    };
+
+    // Mate in two: Kb6 Kb8 Rh8# or Kc7 Ka7 Ra1#.
+    pos.from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1");
+    pos.debug();
+    let result = search(&pos, &[], 6);
+    assert!(result.score == MATE - 3 && mate_in(result.score) == Some(2));
+    let king_moves = [mv_create_normal(C6 as u8, B6 as u8), mv_create_normal(C6 as u8, C7 as u8)];
+    assert!(result.pv.len() == 3 && king_moves.contains(&result.best_move()));
+
+    // And being mated in one.
+    pos.from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1");
+    let result = search(&pos, &[], 4);
+    assert!(result.score == -MATE + 2 && mate_in(result.score) == Some(-1));
+
+    // Checkmated and stalemated positions have no move to return.
+    pos.from_fen("k6R/8/1K6/8/8/8/8/8 b - - 0 1");
+    let result = search(&pos, &[], 4);
+    assert!(result.score == -MATE && result.pv.is_empty());
+    pos.from_fen("k7/8/1Q6/8/8/8/8/K7 b - - 0 1");
+    let result = search(&pos, &[], 4);
+    assert!(result.score == 0 && result.pv.is_empty());
+
+    // A rook up, but any move reaches the fiftieth move without a capture.
+    pos.from_fen("k7/8/8/8/8/8/8/K6R w - - 99 80");
+    let result = search(&pos, &[], 4);
+    assert!(result.score == 0);
+
+    // A rook up, but black can repeat the position by shuffling the king.
+    let mut history = Vec::new();
+    pos.from_fen("7k/8/8/8/8/8/8/KR6 w - - 0 1");
+    let moves = [(B1, B2), (H8, G8), (B2, B1)];
+    for (src, dst) in moves {
+        history.push(pos.hash());
+        pos.make_move(mv_create_normal(src as u8, dst as u8));
+    }
+    let result = search(&pos, &history, 3);
+    assert!(result.best_move() == mv_create_normal(G8 as u8, H8 as u8) && result.score == 0);
+
+    // Qxd5 wins a pawn at depth one, until the quiescence search sees cxd5.
+    pos.from_fen("k7/8/2p5/3p4/8/8/8/K2Q4 w - - 0 1");
+    let result = search(&pos, &[], 1);
+    assert!(result.best_move() != mv_create_normal(D1 as u8, D5 as u8) && result.score > 700);
+
+    // The hash move comes first, and Qxd5 last as it loses the queen.
+    let heuristics = Heuristics::default();
+    let mut picker = MovePicker::new(&pos, mv_create_normal(D1 as u8, D4 as u8), [NULL_MOVE; 2], NULL_MOVE);
+    let mut picked = Vec::new();
+    while let Some(mv) = picker.next(&heuristics) {
+        picked.push(mv);
+    }
+    assert!(picked.len() == perft::<Standard>(1, &mut pos));
+    assert!(picked[0] == mv_create_normal(D1 as u8, D4 as u8));
+    assert!(picked.last() == Some(&mv_create_normal(D1 as u8, D5 as u8)));
+
+    // Mate scores are stored relative to the position, not the root.
+    let tt = TranspositionTable::new(1);
+    tt.store(pos.hash(), 3, NULL_MOVE, MATE - 5, -1, Bound::Lower);
+    let entry = tt.probe(pos.hash(), 1).unwrap();
+    assert!(entry.score == MATE - 3 && entry.depth == -1 && entry.bound == Bound::Lower);
+    assert!(tt.probe(pos.hash() ^ 1, 1).is_none());
+
+    // Too deep to store is stored as deep as can be, not as a negative depth.
+    tt.store(pos.hash(), 0, NULL_MOVE, 0, 128, Bound::Exact);
+    assert!(tt.probe(pos.hash(), 0).unwrap().depth == i8::MAX as i32);
+
+    // A search again from the same position finds its results in the table.
+    pos.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    pos.debug();
+    let first = search(&pos, &[], 5);
+    let second = search(&pos, &[], 5);
+    assert!(second.nodes < first.nodes && second.best_move() == first.best_move());
+    println!("hashfull {}", first.hashfull);
+
+    // What each selective feature saves, switched off on its own.
+    let limits = Limits {
+        depth: 7,
+        ..Limits::default()
+    };
+    let nodes = |options: SearchOptions| {
+        let mut searcher = Search::new();
+        searcher.set_options(options);
+        searcher.search(&pos, &[], limits.clone(), |_| {}).nodes
+    };
+    let all = nodes(SearchOptions::default());
+    let none = nodes(SearchOptions::none());
+    println!("all {} none {}", all, none);
+    assert!(all < none);
+    let all_but = SearchOptions::default;
+    let features = [
+        ("null move", SearchOptions { null_move: false, ..all_but() }),
+        ("lmr", SearchOptions { lmr: false, ..all_but() }),
+        ("futility", SearchOptions { futility: false, ..all_but() }),
+        ("reverse futility", SearchOptions { reverse_futility: false, ..all_but() }),
+        ("razoring", SearchOptions { razoring: false, ..all_but() }),
+        ("check extensions", SearchOptions { check_extensions: false, ..all_but() }),
+        ("iir", SearchOptions { iir: false, ..all_but() }),
+    ];
+    for (name, options) in features {
+        println!("without {}: {}", name, nodes(options));
+    }
+
+    // On the clock, a forced move is played after the first iteration, and
+    // otherwise the search keeps within the time it's given.
+    let clock = Clock {
+        time: Duration::from_secs(3),
+        increment: Duration::ZERO,
+        movestogo: None,
+    };
+    let time = TimeManager::new(&clock, DEFAULT_MOVE_OVERHEAD);
+    assert!(time.soft_limit() <= time.hard_limit() && time.hard_limit() < clock.time);
+    let limits = Limits {
+        clock: Some(clock),
+        ..Limits::default()
+    };
+    pos.from_fen("k7/8/8/8/8/8/1q6/K7 w - - 0 1");
+    let result = Search::new().search(&pos, &[], limits.clone(), |_| {});
+    assert!(result.depth == 1 && result.best_move() == mv_create_normal(A1 as u8, B2 as u8));
+    let movetime = Limits {
+        movetime: Some(Duration::from_secs(5)),
+        ..Limits::default()
+    };
+    let result = Search::new().search(&pos, &[], movetime, |_| {});
+    assert!(result.depth == 1 && result.time < Duration::from_secs(1));
+    pos.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let result = Search::new().search(&pos, &[], limits, |_| {});
+    println!("on a 3s clock: depth {} in {:?}", result.depth, result.time);
+    assert!(result.time <= time.hard_limit() + Duration::from_millis(50));
+
+    // MultiPV finds different moves for each line, best first, and no more
+    // lines than there are moves.
+    let mut searcher = Search::new();
+    let limits = Limits {
+        depth: 6,
+        multipv: 3,
+        ..Limits::default()
+    };
+    let mut reported = 0;
+    let result = searcher.search(&pos, &[], limits.clone(), |_| reported += 1);
+    let lines = searcher.lines();
+    assert!(reported == 18 && lines.len() == 3 && lines[0].pv == result.pv);
+    for (k, line) in lines.iter().enumerate() {
+        println!("multipv {} score {} pv {}", line.multipv, line.score, mv_to_str(line.best_move(), false));
+        assert!(line.multipv == k + 1 && line.depth == 6);
+        assert!(lines[..k].iter().all(|better| better.score >= line.score && better.best_move() != line.best_move()));
+    }
+    pos.from_fen("k7/8/8/8/8/8/1q6/K7 w - - 0 1");
+    searcher.search(&pos, &[], limits, |_| {});
+    assert!(searcher.lines().len() == 1);
+
+    // Helper threads share the table and stop with the main search, whose
+    // count includes their nodes.
+    pos.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let mut searcher = Search::new();
+    searcher.set_threads(4);
+    searcher.resize_tt(8);
+    assert!(searcher.threads() == 4 && Arc::strong_count(searcher.tt()) == 4);
+    let limits = Limits {
+        movetime: Some(Duration::from_millis(500)),
+        ..Limits::default()
+    };
+    let mut last_nodes = 0;
     let start = Instant::now();
-    if depth == 1 {
-        total_nodes = move_generator.moves.len();
-    } else if depth > 1 {
-        for mv in move_generator.moves {
-            let src = mv_get_src(mv);
-            let dst = mv_get_dst(mv);
-
-            let undo = pos.make_move(mv);
-            let nodes = perft(depth - 1, pos);
-            total_nodes += nodes;
-            pos.unmake_move(undo);
-
-            println!("{}{}:  {}", sq_to_str(src), sq_to_str(dst), nodes);
+    let result = searcher.search(&pos, &[], limits, |info| last_nodes = info.nodes);
+    println!("4 threads: depth {} nodes {} in {:?}", result.depth, result.nodes, start.elapsed());
+    assert!(result.nodes >= last_nodes && start.elapsed() < Duration::from_millis(600));
+    assert!(pos.parse_move(&mv_to_str(result.best_move(), false)) == Some(result.best_move()));
+}
+
+// An output that keeps every line for the test to read back.
+fn recorded() -> (Output, Arc<Mutex<Vec<String>>>) {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let record = lines.clone();
+    (Arc::new(move |line: &str| record.lock().unwrap().push(line.to_string())), lines)
+}
+
+// Waits for a line starting with prefix, e.g. from a search thread.
+fn wait_for(lines: &Mutex<Vec<String>>, prefix: &str) -> String {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(10) {
+        if let Some(line) = lines.lock().unwrap().iter().find(|line| line.starts_with(prefix)) {
+            return line.clone();
         }
+        thread::sleep(Duration::from_millis(1));
     }
-    let usec = 1 + start.elapsed().as_micros();
-    let sec = usec as f64 / 1000000.;
-    let milli = usec as f64 / 1000.;
-    let knps = (total_nodes as f64 / sec as f64) / 1000.;
+    panic!("no {} line", prefix);
+}
 
-    println!(
-        "{} nodes in {} ms; {} knps\n",
-        total_nodes, milli, knps as u64
-    );
-    total_nodes
+fn uci_suite() {
+    let (output, lines) = recorded();
+    let mut uci = Uci::with_output(output);
+    let last = || lines.lock().unwrap().last().cloned().unwrap_or_default();
+
+    assert!(uci.command("uci") && last() == "uciok");
+    assert!(uci.command("isready") && last() == "readyok");
+
+    // Moves are played from the position given, keeping the hashes of the
+    // positions before them for repetitions.
+    uci.command("position startpos moves e2e4 e7e5 g1f3");
+    assert!(uci.position().to_fen() == "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    assert!(uci.history().len() == 3);
+    uci.command("position fen r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1 moves e1g1");
+    assert!(uci.position().to_fen() == "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R4RK1 b kq - 1 1");
+    assert!(uci.history().len() == 1);
+
+    // An illegal move stops the list there.
+    uci.command("position startpos moves e2e4 e2e4 e7e5");
+    assert!(last() == "info string illegal move e2e4");
+    assert!(uci.position().to_fen() == "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    uci.command("position e2e4");
+    assert!(last() == "info string expected startpos or fen");
+
+    // A FEN that can't be played from is turned away, leaving the position
+    // as it was.
+    let before = uci.position().to_fen();
+    for (fen, error) in [
+        ("garbage", "expected board, side, castling and en passant fields"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1", "expected eight ranks"),
+        ("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "bad rank"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1", "bad rank"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1", "side to move must be w or b"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1", "castling without a rook"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w KQkq - 0 1", "castling without a king on the back rank"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1", "bad en passant square"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1", "bad move counter"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1", "each side needs one king"),
+        ("rnbqkbnP/pppppppp/8/8/8/8/PPPPPPP1/RNBQKBNR w Qq - 0 1", "pawn on a back rank"),
+        ("4k3/8/8/8/4R3/8/8/4K3 w - - 0 1", "the side not to move is in check"),
+    ] {
+        uci.command(&format!("position fen {} moves e2e4", fen));
+        assert!(last() == format!("info string illegal FEN: {}", error));
+        assert!(uci.position().to_fen() == before && uci.history().len() == 1);
+    }
+
+    uci.command("setoption name Nonsense value 1");
+    assert!(last() == "info string unknown option Nonsense 1");
+    uci.command("frobnicate");
+    assert!(last() == "info string unknown command frobnicate");
+
+    // A search to a fixed depth reports each line and then its move.
+    uci.command("setoption name MultiPV value 2");
+    uci.command("position startpos");
+    uci.command("go depth 4");
+    let bestmove = wait_for(&lines, "bestmove");
+    assert!(uci.position().parse_move(bestmove.split_whitespace().nth(1).unwrap()).is_some());
+    assert!(lines.lock().unwrap().iter().any(|line| line.starts_with("info depth 4 multipv 2 ")));
+
+    // An infinite search only gives its move once told to stop.
+    lines.lock().unwrap().clear();
+    uci.command("go infinite");
+    thread::sleep(Duration::from_millis(100));
+    assert!(!lines.lock().unwrap().iter().any(|line| line.starts_with("bestmove")));
+    uci.command("stop");
+    assert!(last().starts_with("bestmove"));
+
+    // Perft lists the count below each move, then the total.
+    lines.lock().unwrap().clear();
+    uci.command("position fen r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    uci.command("go perft 2");
+    assert!(wait_for(&lines, "2039 nodes in ").ends_with(" knps"));
+    let lines_seen = lines.lock().unwrap().clone();
+    assert!(lines_seen.len() == 50 && lines_seen[0] == "node count, depth==2");
+    for expected in ["e1g1:  43", "e1c1:  43", "d5e6:  46", "e5f7:  44", "a2a3:  44"] {
+        assert!(lines_seen.iter().any(|line| line == expected));
+    }
+    uci.command("position startpos");
+
+    // Illegal searchmoves are reported, and don't stop the list there.
+    lines.lock().unwrap().clear();
+    uci.command("go searchmoves a1a1 b1c3 e2e5 depth 3");
+    assert!(wait_for(&lines, "bestmove").starts_with("bestmove b1c3"));
+    assert!(lines.lock().unwrap()[..2] == ["info string illegal searchmoves move a1a1", "info string illegal searchmoves move e2e5"]);
+    lines.lock().unwrap().clear();
+    uci.command("go searchmoves a1a1 depth 1");
+    wait_for(&lines, "bestmove");
+    assert!(lines.lock().unwrap()[1] == "info string no legal searchmoves, searching every move");
+    uci.command("stop");
+
+    assert!(!uci.command("quit"));
+}
+
+fn xboard_suite() {
+    let (output, lines) = recorded();
+    let mut xboard = XBoard::with_output(output);
+    let last = || lines.lock().unwrap().last().cloned().unwrap_or_default();
+
+    xboard.command("xboard");
+    xboard.command("protover 2");
+    assert!(last() == "feature done=1");
+    xboard.command("ping 7");
+    assert!(last() == "pong 7");
+
+    // In force mode moves are only played.  undo takes back one and remove
+    // two, as far back as the game goes.
+    xboard.command("new");
+    xboard.command("force");
+    for mv in ["e2e4", "e7e5", "g1f3"] {
+        xboard.command(&format!("usermove {}", mv));
+    }
+    assert!(xboard.position().to_fen() == "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    assert!(xboard.history().len() == 3);
+    xboard.command("undo");
+    assert!(xboard.position().to_fen() == "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+    xboard.command("remove");
+    xboard.command("remove");
+    assert!(xboard.position().to_fen() == "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    assert!(xboard.history().is_empty());
+    xboard.command("usermove e2e5");
+    assert!(last() == "Illegal move: e2e5");
+
+    // A position that can't be set up is reported, and the game carries on.
+    xboard.command("usermove e2e4");
+    xboard.command("setboard 8/8/8/8/8/8/8/k6K w");
+    assert!(last() == "tellusererror Illegal position: expected board, side, castling and en passant fields");
+    xboard.command("setboard 4k3/8/8/8/4R3/8/8/4K3 w - - 0 1");
+    assert!(last() == "tellusererror Illegal position: the side not to move is in check");
+    assert!(xboard.position().to_fen() == "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    xboard.command("undo");
+    assert!(xboard.history().is_empty());
+
+    // The session is counted from the level command, not the move number.
+    xboard.command("setboard r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 30");
+    xboard.command("level 40 5:30 2");
+    let clock = xboard.limits().clock.unwrap();
+    assert!(clock.time == Duration::from_secs(330) && clock.increment == Duration::from_secs(2));
+    assert!(clock.movestogo == Some(40));
+    xboard.command("usermove e1g1");
+    xboard.command("usermove a6e2");
+    xboard.command("time 12345");
+    let clock = xboard.limits().clock.unwrap();
+    assert!(clock.time == Duration::from_millis(123450) && clock.movestogo == Some(39));
+    xboard.command("remove");
+    assert!(xboard.limits().clock.unwrap().movestogo == Some(40));
+    xboard.command("level 0 2 0");
+    let clock = xboard.limits().clock.unwrap();
+    assert!(clock.time == Duration::from_secs(120) && clock.movestogo.is_none());
+
+    // A fractional increment is kept, and one that's no use is none at all.
+    xboard.command("level 0 2 0.5");
+    assert!(xboard.limits().clock.unwrap().increment == Duration::from_millis(500));
+    for increment in ["inf", "NaN", "-1", "x"] {
+        xboard.command(&format!("level 0 2 {}", increment));
+        assert!(xboard.limits().clock.unwrap().increment == Duration::ZERO);
+    }
+
+    // A fixed time per move takes over from the clock.
+    xboard.command("st 5");
+    xboard.command("sd 6");
+    let limits = xboard.limits();
+    assert!(limits.movetime == Some(Duration::from_secs(5) - DEFAULT_MOVE_OVERHEAD));
+    assert!(limits.clock.is_none() && limits.depth == 6);
+
+    // Out of force mode the engine answers a move with its own, which is
+    // announced before the pong for a ping sent after it.
+    xboard.command("new");
+    xboard.command("sd 2");
+    lines.lock().unwrap().clear();
+    xboard.command("usermove e2e4");
+    let reply = wait_for(&lines, "move ");
+    xboard.command("ping 8");
+    assert!(last() == "pong 8" && lines.lock().unwrap()[0] == reply);
+    xboard.command("force");
+    assert!(xboard.history().len() == 2 && xboard.position().side == White);
+
+    // With a fixed time per move, a forced move is played at once.  Nd1 is
+    // the only way out of check, with plenty still to search behind it.
+    xboard.command("setboard rnbqkbn1/pppppppp/8/8/8/4N3/3PPPPP/r3K3 w - - 0 1");
+    xboard.command("st 5");
+    xboard.command("sd 100");
+    lines.lock().unwrap().clear();
+    let start = Instant::now();
+    xboard.command("go");
+    assert!(wait_for(&lines, "move ") == "move e3d1" && start.elapsed() < Duration::from_secs(1));
+
+    assert!(!xboard.command("quit"));
 }
 
 fn main() {
@@ -72,28 +955,138 @@ fn main() {
     let position5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
     let position6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
 
+    // eval [json] <fen>: print the evaluation trace for a position.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "eval") {
+        let json = args.get(1).is_some_and(|arg| arg == "json");
+        let fen = args[if json { 2 } else { 1 }..].join(" ");
+        let mut pos = Position::new();
+        pos.from_fen(if fen.is_empty() { position1 } else { &fen });
+        if json {
+            println!("{}", pos.trace().to_json());
+        } else {
+            pos.debug_eval();
+        }
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "see") {
+        see_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "incremental") {
+        incremental_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "pawns") {
+        pawns_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "kingsafety") {
+        king_safety_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "trace") {
+        trace_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "chess960") {
+        chess960_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "crazyhouse") {
+        crazyhouse_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "bughouse") {
+        bughouse_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "antichess") {
+        antichess_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "3check") {
+        three_check_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "koth") {
+        koth_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "racingkings") {
+        racing_kings_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "horde") {
+        horde_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "atomic") {
+        atomic_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "search") {
+        search_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "uci") {
+        uci_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "xboard") {
+        xboard_suite();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "bench") {
+        bench(&[
+            (position1, 5),
+            (position2, 4),
+            (position3, 6),
+            (position4, 4),
+        ]);
+        return;
+    }
+
     let mut pos = Position::new();
     pos.from_fen(position1);
     pos.debug();
-    assert!(119060324 == divide(6, &mut pos));
+    assert!(119060324 == divide::<Standard>(6, &mut pos));
 
     pos.from_fen(position2);
     pos.debug();
-    assert!(193690690 == divide(5, &mut pos));
+    assert!(193690690 == divide::<Standard>(5, &mut pos));
 
     pos.from_fen(position3);
     pos.debug();
-    assert!(178633661 == divide(7, &mut pos));
+    assert!(178633661 == divide::<Standard>(7, &mut pos));
 
     pos.from_fen(position4);
     pos.debug();
-    assert!(15833292 == divide(5, &mut pos));
+    assert!(15833292 == divide::<Standard>(5, &mut pos));
 
     pos.from_fen(position5);
     pos.debug();
-    assert!(89941194 == divide(5, &mut pos));
+    assert!(89941194 == divide::<Standard>(5, &mut pos));
 
     pos.from_fen(position6);
     pos.debug();
-    assert!(6923051137 == divide(6, &mut pos));
+    assert!(6923051137 == divide::<Standard>(6, &mut pos));
 }
@@ -0,0 +1,258 @@
+use crate::moves::*;
+use crate::position::Position;
+use crate::search::MAX_PLY;
+use crate::see::*;
+use crate::things::*;
+
+const MAX_HISTORY: i32 = 16384;
+
+// Material won by a capture or promotion, before any recapture.
+pub fn captured_value(pos: &Position, mv: u16) -> i32 {
+    let kind = mv_get_kind(mv);
+    let mut value = match kind {
+        MOVE_ENPASSANT => SEE_VALUES[Pawn as usize],
+        MOVE_CASTLE | MOVE_DROP => 0,
+        _ => match pos.piece_on(mv_get_dst(mv)) {
+            NoPiece => 0,
+            piece => SEE_VALUES[piece as usize],
+        },
+    };
+    if kind == MOVE_PROMO {
+        value += SEE_VALUES[mv_get_promo_piece(mv) as usize] - SEE_VALUES[Pawn as usize];
+    }
+    value
+}
+
+// Most valuable victim first, least valuable attacker breaking ties.
+pub fn mvv_lva(pos: &Position, mv: u16) -> i32 {
+    8 * captured_value(pos, mv) - pos.piece_on(mv_get_src(mv)) as i32
+}
+
+pub fn is_quiet(pos: &Position, mv: u16) -> bool {
+    match mv_get_kind(mv) {
+        MOVE_PROMO | MOVE_ENPASSANT => false,
+        MOVE_CASTLE | MOVE_DROP => true,
+        _ => pos.piece_on(mv_get_dst(mv)) == NoPiece,
+    }
+}
+
+// What the search has learned about which quiet moves tend to be good.
+pub struct Heuristics {
+    // Two quiet moves per ply that recently caused a cutoff there.
+    pub killers: [[u16; 2]; MAX_PLY],
+    // The quiet move that last refuted each move, indexed by its squares.
+    pub counters: [[u16; 64]; 64],
+    // How often each quiet move, by color and squares, has caused a cutoff
+    // rather than failed to.
+    pub butterfly: [[[i32; 64]; 64]; NUM_COLORS],
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Heuristics {
+            killers: [[NULL_MOVE; 2]; MAX_PLY],
+            counters: [[NULL_MOVE; 64]; 64],
+            butterfly: [[[0; 64]; 64]; NUM_COLORS],
+        }
+    }
+}
+
+impl Heuristics {
+    pub fn butterfly(&self, color: Color, mv: u16) -> i32 {
+        self.butterfly[color as usize][mv_get_src(mv) as usize][mv_get_dst(mv) as usize]
+    }
+
+    pub fn counter(&self, prev: u16) -> u16 {
+        self.counters[mv_get_src(prev) as usize][mv_get_dst(prev) as usize]
+    }
+
+    // A quiet move caused a cutoff, after the other quiet moves tried before
+    // it didn't.
+    pub fn update_quiet(&mut self, color: Color, ply: usize, prev: u16, mv: u16, depth: i32, tried: &[u16]) {
+        let killers = &mut self.killers[ply];
+        if killers[0] != mv {
+            killers[1] = killers[0];
+            killers[0] = mv;
+        }
+        if prev != NULL_MOVE {
+            self.counters[mv_get_src(prev) as usize][mv_get_dst(prev) as usize] = mv;
+        }
+
+        // Scaled so that entries settle below MAX_HISTORY rather than grow
+        // without bound.
+        let bonus = (depth * depth).min(400);
+        let table = &mut self.butterfly[color as usize];
+        for &other in tried {
+            let entry = &mut table[mv_get_src(other) as usize][mv_get_dst(other) as usize];
+            *entry -= bonus + *entry * bonus / MAX_HISTORY;
+        }
+        let entry = &mut table[mv_get_src(mv) as usize][mv_get_dst(mv) as usize];
+        *entry += bonus - *entry * bonus / MAX_HISTORY;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+    HashMove,
+    GenCaptures,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    GenQuiets,
+    Quiets,
+    BadCaptures,
+    Evasions,
+    Done,
+}
+
+// Hands out the legal moves in the order they're most likely to cause a
+// cutoff, generating each batch only when it's reached: the hash move, then
+// captures that don't lose material, killers, the counter-move, quiet moves by
+// history, and captures that do lose material.  In check every evasion is
+// generated at once.
+pub struct MovePicker {
+    pos: Position,
+    stage: Stage,
+    hash_move: u16,
+    killers: [u16; 2],
+    counter: u16,
+    moves: MoveList,
+    scores: [i32; MAX_MOVES],
+    index: usize,
+    bad_captures: MoveList,
+}
+
+impl MovePicker {
+    pub fn new(pos: &Position, hash_move: u16, killers: [u16; 2], counter: u16) -> MovePicker {
+        MovePicker {
+            pos: *pos,
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            counter,
+            moves: MoveList::new(),
+            scores: [0; MAX_MOVES],
+            index: 0,
+            bad_captures: MoveList::new(),
+        }
+    }
+
+    pub fn next(&mut self, heuristics: &Heuristics) -> Option<u16> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenCaptures;
+                    if self.hash_move != NULL_MOVE && MoveGen::new(&self.pos).is_legal(self.hash_move) {
+                        return Some(self.hash_move);
+                    }
+                }
+                Stage::GenCaptures => {
+                    let mut move_generator = MoveGen::new(&self.pos);
+                    move_generator.gen_captures();
+                    self.moves = move_generator.moves;
+                    self.index = 0;
+                    self.stage = if self.pos.calc_checkers() != 0 {
+                        Stage::Evasions
+                    } else {
+                        Stage::GoodCaptures
+                    };
+
+                    // Evasions include quiet moves; they go after captures.
+                    let us = self.pos.us();
+                    for (i, &mv) in self.moves.iter().enumerate() {
+                        self.scores[i] = if is_quiet(&self.pos, mv) {
+                            heuristics.butterfly(us, mv) - 2 * MAX_HISTORY
+                        } else {
+                            mvv_lva(&self.pos, mv)
+                        };
+                    }
+                }
+                Stage::GoodCaptures => match self.select() {
+                    Some(mv) if mv == self.hash_move => {}
+                    Some(mv) if !self.pos.see_ge(mv, 0) => self.bad_captures.push(mv),
+                    Some(mv) => return Some(mv),
+                    None => {
+                        self.stage = Stage::Killers;
+                        self.index = 0;
+                    }
+                },
+                Stage::Killers => {
+                    if self.index == self.killers.len() {
+                        self.stage = Stage::CounterMove;
+                        continue;
+                    }
+                    let killer = self.killers[self.index];
+                    self.index += 1;
+                    let repeated = self.index == 2 && killer == self.killers[0];
+                    if killer != self.hash_move && !repeated && self.is_legal_quiet(killer) {
+                        return Some(killer);
+                    }
+                }
+                Stage::CounterMove => {
+                    self.stage = Stage::GenQuiets;
+                    let counter = self.counter;
+                    if counter != self.hash_move && !self.killers.contains(&counter) && self.is_legal_quiet(counter) {
+                        return Some(counter);
+                    }
+                }
+                Stage::GenQuiets => {
+                    let mut move_generator = MoveGen::new(&self.pos);
+                    move_generator.gen_quiets();
+                    self.moves = move_generator.moves;
+                    self.index = 0;
+                    self.stage = Stage::Quiets;
+
+                    let us = self.pos.us();
+                    for (i, &mv) in self.moves.iter().enumerate() {
+                        self.scores[i] = heuristics.butterfly(us, mv);
+                    }
+                }
+                Stage::Quiets => match self.select() {
+                    Some(mv) if mv == self.hash_move || self.killers.contains(&mv) || mv == self.counter => {}
+                    Some(mv) => return Some(mv),
+                    None => {
+                        self.stage = Stage::BadCaptures;
+                        self.index = 0;
+                    }
+                },
+                Stage::BadCaptures => {
+                    // Already in MVV-LVA order.
+                    if self.index == self.bad_captures.len() {
+                        self.stage = Stage::Done;
+                        continue;
+                    }
+                    self.index += 1;
+                    return Some(self.bad_captures[self.index - 1]);
+                }
+                Stage::Evasions => match self.select() {
+                    Some(mv) if mv == self.hash_move => {}
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    // The best scoring of the moves not yet handed out.  A full sort would be
+    // wasted when a cutoff comes after the first move or two.
+    fn select(&mut self) -> Option<u16> {
+        if self.index == self.moves.len() {
+            return None;
+        }
+        let mut best = self.index;
+        for i in self.index + 1..self.moves.len() {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+        self.index += 1;
+        Some(self.moves[self.index - 1])
+    }
+
+    fn is_legal_quiet(&self, mv: u16) -> bool {
+        mv != NULL_MOVE && is_quiet(&self.pos, mv) && MoveGen::new(&self.pos).is_legal(mv)
+    }
+}
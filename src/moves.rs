@@ -3,26 +3,39 @@ use crate::bitboard::*;
 use crate::position::*;
 use crate::square::*;
 use crate::things::*;
+use crate::variant::*;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
 
 pub const MOVE_NORMAL: u8 = 0;
 pub const MOVE_PROMO: u8 = 1;
 pub const MOVE_ENPASSANT: u8 = 2;
 pub const MOVE_CASTLE: u8 = 3;
+pub const MOVE_DROP: u8 = 4;
+pub const NULL_MOVE: u16 = 0; // a1a1 is never a real move
 pub const PROMOTION_RANKS: [u8; 2] = [Rank8 as u8, Rank1 as u8];
+pub const MAX_MOVES: usize = 512;
 
 fn mv_create(src: u8, dst: u8, kind: u8, piece: u8) -> u16 {
     let src = src as u16;
     let dst = dst as u16;
     let kind = kind as u16;
     let piece = piece as u16;
-    (((piece - 1) << 14) | (kind << 12) | (dst << 6) | src) as u16
+    ((piece - 1) << 14) | (kind << 12) | (dst << 6) | src
 }
 
 pub fn mv_create_normal(src: u8, dst: u8) -> u16 {
     mv_create(src, dst, MOVE_NORMAL, Knight as u8)
 }
 
+// There are only two bits for the promotion piece, which covers knight to
+// queen.  Antichess also allows promoting to a king; since castling moves
+// always leave the piece bits clear, a king promotion is stored as a castle
+// with the piece bits set.
 pub fn mv_create_promo(src: u8, dst: u8, piece: u8) -> u16 {
+    if piece == King as u8 {
+        return mv_create(src, dst, MOVE_CASTLE, Bishop as u8);
+    }
     mv_create(src, dst, MOVE_PROMO, piece)
 }
 
@@ -34,6 +47,25 @@ pub fn mv_create_ep(src: u8, dst: u8) -> u16 {
     mv_create(src, dst, MOVE_ENPASSANT, Knight as u8)
 }
 
+// A piece dropped from the pocket has no source square, so drops are encoded
+// with src == dst, which no other move has, and the dropped piece in the bits
+// normally used for the kind and promotion piece.  That makes the null move
+// look like a pawn dropped on a1, which can never be legal, so it isn't
+// counted as a drop.
+pub fn mv_create_drop(dst: u8, piece: Piece) -> u16 {
+    let dst = dst as u16;
+    let piece = piece as u16;
+    (piece << 12) | (dst << 6) | dst
+}
+
+pub fn mv_is_drop(mv: u16) -> bool {
+    mv != NULL_MOVE && mv_get_src(mv) == mv_get_dst(mv)
+}
+
+pub fn mv_get_drop_piece(mv: u16) -> Piece {
+    unsafe { std::mem::transmute(((mv >> 12) & 0xf) as u8) }
+}
+
 pub fn mv_get_src(mv: u16) -> u8 {
     (mv & 0x3f).try_into().unwrap()
 }
@@ -43,47 +75,191 @@ pub fn mv_get_dst(mv: u16) -> u8 {
 }
 
 pub fn mv_get_kind(mv: u16) -> u8 {
-    ((mv >> 12) & 0x3).try_into().unwrap()
+    if mv_is_drop(mv) {
+        return MOVE_DROP;
+    }
+    let kind: u8 = ((mv >> 12) & 0x3).try_into().unwrap();
+    if kind == MOVE_CASTLE && mv >> 14 != 0 {
+        return MOVE_PROMO;
+    }
+    kind
 }
 
 pub fn mv_get_promo_piece(mv: u16) -> Piece {
+    if (mv >> 12) & 0x3 == MOVE_CASTLE as u16 {
+        return King;
+    }
     unsafe { std::mem::transmute(1 + ((mv >> 14) & 0x3) as u8) }
 }
 
+// Coordinate notation, e.g. e2e4 or e7e8q.  Castling moves are stored as the
+// king capturing its own rook; that's also how they're written in Chess960,
+// but standard chess writes the king's destination instead.
+pub fn mv_to_str(mv: u16, chess960: bool) -> String {
+    let src = mv_get_src(mv);
+    let mut dst = mv_get_dst(mv);
+    let kind = mv_get_kind(mv);
+
+    if kind == MOVE_DROP {
+        let mut s = piece_to_str(mv_get_drop_piece(mv), White);
+        s.push('@');
+        s.push_str(&sq_to_str(dst));
+        return s;
+    }
+
+    if kind == MOVE_CASTLE && !chess960 {
+        let file = if file_of(dst) > file_of(src) { FileG } else { FileC };
+        dst = make_sq(rank_of(src), file as u8);
+    }
+
+    let mut s = sq_to_str(src);
+    s.push_str(&sq_to_str(dst));
+    if kind == MOVE_PROMO {
+        s.push_str(&piece_to_str(mv_get_promo_piece(mv), Black));
+    }
+    s
+}
+
 pub struct Undo {
     pub mv: u16,
     pub captured: Piece,
     pub castle: u8,
     pub half: i32,
     pub ep: u8,
+    pub promoted: u64, // promoted pieces before the move (crazyhouse)
+    pub exploded: u64, // what the move's explosion cleared (atomic)
+}
+
+// Fixed-capacity list of moves which lives on the stack, so generating moves
+// doesn't need to allocate.  No standard chess position has more than 218
+// moves, but a crazyhouse position with a full pocket can have a lot more.
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [u16; MAX_MOVES],
+    len: usize,
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MoveList {
+    pub fn new() -> MoveList {
+        MoveList {
+            moves: [0; MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, mv: u16) {
+        debug_assert!(self.len < MAX_MOVES);
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    // Keep only the moves for which keep() returns true, preserving order.
+    pub fn retain<F: FnMut(u16) -> bool>(&mut self, mut keep: F) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            let mv = self.moves[i];
+            if keep(mv) {
+                self.moves[kept] = mv;
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
 }
 
-pub struct MoveGen {
-    position: Position,
+impl Deref for MoveList {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [u16] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = u16;
+    type IntoIter = std::iter::Take<std::array::IntoIter<u16, MAX_MOVES>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.moves.into_iter().take(self.len)
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a u16;
+    type IntoIter = std::slice::Iter<'a, u16>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<u16> for MoveList {
+    fn from_iter<I: IntoIterator<Item = u16>>(iter: I) -> MoveList {
+        let mut list = MoveList::new();
+        for mv in iter {
+            list.push(mv);
+        }
+        list
+    }
+}
+
+pub struct MoveGen<'a, V: Variant = Standard> {
+    position: &'a Position,
     attacked: u64,
     occupancy: u64,
     our_pieces: u64,
     their_pieces: u64,
     our_pinned_pieces: u64,
     their_checkers: u64,
-    pub moves: Vec<u16>,
+    pub moves: MoveList,
+    variant: PhantomData<V>,
+}
+
+impl<'a> MoveGen<'a> {
+    pub fn new(position: &'a Position) -> MoveGen<'a> {
+        MoveGen::for_variant(position)
+    }
 }
 
-impl MoveGen {
-    pub fn new(position: Position) -> MoveGen {
+impl<'a, V: Variant> MoveGen<'a, V> {
+    pub fn for_variant(position: &'a Position) -> MoveGen<'a, V> {
+        // Without king safety there's no need to work out attacks, pins or
+        // checks; leaving them empty makes the generators pseudo-legal.
+        let legal = !V::PSEUDO_LEGAL;
         MoveGen {
-            position: position,
-            attacked: all_attacks(position, position.enemy()),
+            position,
+            attacked: if legal { all_attacks(position, position.enemy()) } else { 0 },
             occupancy: position.occupancy(),
             our_pieces: position.our_pieces(),
             their_pieces: position.their_pieces(),
-            our_pinned_pieces: position.calc_pinned(),
-            their_checkers: position.calc_checkers(),
-            moves: Vec::with_capacity(256)
+            our_pinned_pieces: if legal { position.calc_pinned() } else { 0 },
+            their_checkers: if legal { position.calc_checkers() } else { 0 },
+            moves: MoveList::new(),
+            variant: PhantomData,
         }
     }
 
     pub fn gen_legal_moves(&mut self) {
+        if V::variant_outcome(self.position).is_some() {
+            return;
+        }
+
         if self.position.our_king() & self.attacked != 0 {
             self.gen_get_out_of_check_moves();
         } else {
@@ -95,7 +271,111 @@ impl MoveGen {
             self.gen_queen_moves(all);
             self.gen_king_moves(all);
             self.gen_castling_moves();
+            if V::DROPS {
+                self.gen_drop_moves(all);
+            }
+        }
+
+        if V::PSEUDO_LEGAL {
+            let position = self.position;
+            self.moves.retain(|mv| V::is_legal(position, mv));
+        }
+        V::filter_moves(self.position, &mut self.moves);
+    }
+
+    // Captures and promotions only, for the quiescence search; or every move
+    // out of check, since there's no standing pat when in check.
+    pub fn gen_captures(&mut self) {
+        if V::variant_outcome(self.position).is_some() {
+            return;
+        }
+
+        if self.position.our_king() & self.attacked != 0 {
+            self.gen_get_out_of_check_moves();
+        } else {
+            let targets = self.their_pieces;
+            let promotions = RANK_BITBOARDS[PROMOTION_RANKS[self.position.us() as usize] as usize];
+            self.gen_pawn_captures(targets);
+            self.gen_pawn_advances(promotions);
+            self.gen_knight_moves(targets);
+            self.gen_bishop_moves(targets);
+            self.gen_rook_moves(targets);
+            self.gen_queen_moves(targets);
+            self.gen_king_moves(targets);
+        }
+
+        if V::PSEUDO_LEGAL {
+            let position = self.position;
+            self.moves.retain(|mv| V::is_legal(position, mv));
+        }
+        V::filter_moves(self.position, &mut self.moves);
+    }
+
+    // Everything gen_captures() leaves out.  Out of check that's the quiet
+    // moves; in check gen_captures() has already generated every evasion.
+    pub fn gen_quiets(&mut self) {
+        if V::variant_outcome(self.position).is_some() || self.position.our_king() & self.attacked != 0 {
+            return;
+        }
+
+        let empty = !self.occupancy;
+        let promotions = RANK_BITBOARDS[PROMOTION_RANKS[self.position.us() as usize] as usize];
+        self.gen_pawn_advances(empty & !promotions);
+        self.gen_knight_moves(empty);
+        self.gen_bishop_moves(empty);
+        self.gen_rook_moves(empty);
+        self.gen_queen_moves(empty);
+        self.gen_king_moves(empty);
+        self.gen_castling_moves();
+        if V::DROPS {
+            self.gen_drop_moves(empty);
+        }
+
+        if V::PSEUDO_LEGAL {
+            let position = self.position;
+            self.moves.retain(|mv| V::is_legal(position, mv));
+        }
+        V::filter_moves(self.position, &mut self.moves);
+    }
+
+    // Is mv legal here?  For checking moves from elsewhere, such as a hash
+    // table, without generating every move: only moves by the same kind of
+    // piece to the same square are generated.
+    pub fn is_legal(&mut self, mv: u16) -> bool {
+        let src_sq = mv_get_src(mv);
+        let dst_sq = mv_get_dst(mv);
+        let kind = mv_get_kind(mv);
+        let found = std::mem::take(&mut self.moves);
+
+        // Filtering pseudo-legal variants' moves can depend on all of them, as
+        // with forced captures, and evasions are few anyway.
+        if V::PSEUDO_LEGAL || self.position.our_king() & self.attacked != 0 {
+            self.gen_legal_moves();
+        } else if V::variant_outcome(self.position).is_some() {
+            // No moves at all.
+        } else if kind == MOVE_DROP {
+            if V::DROPS {
+                self.gen_drop_moves(bb_from_sq(dst_sq));
+            }
+        } else if kind == MOVE_CASTLE {
+            self.gen_castling_moves();
+        } else if self.position.color_on(src_sq) == self.position.us() {
+            let targets = bb_from_sq(dst_sq);
+            match self.position.piece_on(src_sq) {
+                Pawn => self.gen_pawn_moves(targets),
+                Knight => self.gen_knight_moves(targets),
+                Bishop => self.gen_bishop_moves(targets),
+                Rook => self.gen_rook_moves(targets),
+                Queen => self.gen_queen_moves(targets),
+                King => self.gen_king_moves(targets),
+                _ => {}
+            }
+            V::filter_moves(self.position, &mut self.moves);
         }
+
+        let legal = self.moves.contains(&mv);
+        self.moves = found;
+        legal
     }
 
     fn gen_pawn_advances(&mut self, targets: u64) {
@@ -103,19 +383,12 @@ impl MoveGen {
         while pawns != 0 {
             let src_sq = bb_pop(&mut pawns);
             let pawn = bb_from_sq(src_sq);
-            let mut advances = targets & pawn_advances(pawn, self.occupancy, self.position.us());
+            let mut advances = targets & pawn_advances(pawn, self.occupancy, self.position.us(), V::FIRST_RANK_DOUBLE_PUSH);
             while advances != 0 {
                 let dst_sq = bb_pop(&mut advances);
                 if self.passed_pin_check(src_sq, dst_sq) {
                     if rank_of(dst_sq) == PROMOTION_RANKS[self.position.us() as usize] {
-                        let mv_n = mv_create_promo(src_sq, dst_sq, Knight as u8);
-                        let mv_b = mv_create_promo(src_sq, dst_sq, Bishop as u8);
-                        let mv_r = mv_create_promo(src_sq, dst_sq, Rook as u8);
-                        let mv_q = mv_create_promo(src_sq, dst_sq, Queen as u8);
-                        self.moves.push(mv_n);
-                        self.moves.push(mv_b);
-                        self.moves.push(mv_r);
-                        self.moves.push(mv_q);
+                        self.gen_promotions(src_sq, dst_sq);
                     } else {
                         let mv = mv_create_normal(src_sq, dst_sq);
                         self.moves.push(mv);
@@ -139,14 +412,7 @@ impl MoveGen {
                 let dst_sq = bb_pop(&mut attacks);
                 if self.passed_pin_check(src_sq, dst_sq) {
                     if rank_of(dst_sq) == PROMOTION_RANKS[self.position.us() as usize] {
-                        let mv_n = mv_create_promo(src_sq, dst_sq, Knight as u8);
-                        let mv_b = mv_create_promo(src_sq, dst_sq, Bishop as u8);
-                        let mv_r = mv_create_promo(src_sq, dst_sq, Rook as u8);
-                        let mv_q = mv_create_promo(src_sq, dst_sq, Queen as u8);
-                        self.moves.push(mv_n);
-                        self.moves.push(mv_b);
-                        self.moves.push(mv_r);
-                        self.moves.push(mv_q);
+                        self.gen_promotions(src_sq, dst_sq);
                     } else {
                         let mv = mv_create_normal(src_sq, dst_sq);
                         self.moves.push(mv);
@@ -214,7 +480,7 @@ impl MoveGen {
                 // illegal if a rook attack from king's sq reaches an enemy rook or queen
                 let illegal = rook_attacks(self.position.our_king(), occ) & their_straights != 0;
 
-                if !illegal && !pawn_is_pinned {
+                if V::PSEUDO_LEGAL || (!illegal && !pawn_is_pinned) {
                     let mv = mv_create_ep(src_sq, dst_sq);
                     self.moves.push(mv);
                 }
@@ -222,6 +488,21 @@ impl MoveGen {
         }
     }
 
+    fn gen_promotions(&mut self, src_sq: u8, dst_sq: u8) {
+        let mv_n = mv_create_promo(src_sq, dst_sq, Knight as u8);
+        let mv_b = mv_create_promo(src_sq, dst_sq, Bishop as u8);
+        let mv_r = mv_create_promo(src_sq, dst_sq, Rook as u8);
+        let mv_q = mv_create_promo(src_sq, dst_sq, Queen as u8);
+        self.moves.push(mv_n);
+        self.moves.push(mv_b);
+        self.moves.push(mv_r);
+        self.moves.push(mv_q);
+        if V::KING_PROMOTION {
+            let mv_k = mv_create_promo(src_sq, dst_sq, King as u8);
+            self.moves.push(mv_k);
+        }
+    }
+
     fn gen_pawn_moves(&mut self, targets: u64) {
         self.gen_pawn_advances(targets);
         self.gen_pawn_captures(targets);
@@ -313,19 +594,44 @@ impl MoveGen {
         }
     }
 
+    // Antichess kings can be captured, or promoted to so that a side has more
+    // than one, and the horde has none.
     fn gen_king_moves(&mut self, targets: u64) {
-        let king = self.position.our_king();
-        let mut attacks = king_attacks(king);
+        let mut kings = self.position.our_king();
+        while kings != 0 {
+            let src_sq = bb_pop(&mut kings);
+            let mut attacks = king_attacks_from(src_sq);
+
+            attacks &= targets;
+            attacks &= !self.our_pieces;
+            attacks &= !self.attacked;
 
-        attacks &= targets;
-        attacks &= !self.our_pieces;
-        attacks &= !self.attacked;
+            while attacks != 0 {
+                let dst_sq = bb_pop(&mut attacks);
+                let mv = mv_create_normal(src_sq, dst_sq);
+                self.moves.push(mv);
+            }
+        }
+    }
 
-        while attacks != 0 {
-            let src_sq = bb_lsb(king);
-            let dst_sq = bb_pop(&mut attacks);
-            let mv = mv_create_normal(src_sq, dst_sq);
-            self.moves.push(mv);
+    // Drop pieces from our pocket onto empty target squares.  Pawns can't be
+    // dropped on the first or last rank.  Adding a piece to the board can
+    // never expose our king, so there's no need for a pin check.
+    fn gen_drop_moves(&mut self, targets: u64) {
+        let us = self.position.us();
+        let empty = targets & !self.occupancy;
+        let back_ranks = RANK_BITBOARDS[Rank1 as usize] | RANK_BITBOARDS[Rank8 as usize];
+
+        for piece in [Pawn, Knight, Bishop, Rook, Queen] {
+            if self.position.pocket_count(us, piece) == 0 {
+                continue;
+            }
+            let mut squares = if piece == Pawn { empty & !back_ranks } else { empty };
+            while squares != 0 {
+                let dst_sq = bb_pop(&mut squares);
+                let mv = mv_create_drop(dst_sq, piece);
+                self.moves.push(mv);
+            }
         }
     }
 
@@ -379,72 +685,59 @@ impl MoveGen {
             self.gen_bishop_moves(targets);
             self.gen_rook_moves(targets);
             self.gen_queen_moves(targets);
+            if V::DROPS {
+                // A dropped piece can block the check, but can't capture.
+                self.gen_drop_moves(attack_ray);
+            }
         } else {
             // Double check: must move king
         }
     }
 
-    // TODO: replace rank/file lookups with bb squares OR consolidate this code
-    // to eliminate white vs black.
+    // Castling is legal when every square the king and rook pass over or land
+    // on is empty (other than the king and rook themselves), and the king is
+    // not in check, doesn't pass over an attacked square and doesn't land on
+    // one.  This works for Chess960 as well as standard chess.
     fn gen_castling_moves(&mut self) {
-        let rights = self.position.castle;
         let us = self.position.us();
-
-        if (us == White) && (rights & WHITE_OO != 0) {
-            let src_sq = bb_lsb(self.position.our_king());
-            let dst_sq = Squares::G1 as u8;
-            let need_empty = RANK_BITBOARDS[Rank1 as usize]
-                & (FILE_BITBOARDS[FileF as usize] | FILE_BITBOARDS[FileG as usize]);
-            let need_unattacked = self.position.our_king() | need_empty;
-
-            if (self.occupancy & need_empty == 0) && (need_unattacked & self.attacked == 0) {
-                let mv = mv_create_castle(src_sq, dst_sq);
-                self.moves.push(mv);
-            }
+        let rights = if us == White {
+            [WHITE_OO, WHITE_OOO]
+        } else {
+            [BLACK_OO, BLACK_OOO]
+        };
+        let king = self.position.our_king();
+        if king == 0 {
+            return;
         }
-        if (us == White) && (rights & WHITE_OOO != 0) {
-            let src_sq = bb_lsb(self.position.our_king());
-            let dst_sq = Squares::C1 as u8;
-            let need_empty = RANK_BITBOARDS[Rank1 as usize]
-                & (FILE_BITBOARDS[FileB as usize]
-                    | FILE_BITBOARDS[FileC as usize]
-                    | FILE_BITBOARDS[FileD as usize]);
-            let need_unattacked = self.position.our_king()
-                | (RANK_BITBOARDS[Rank1 as usize]
-                    & (FILE_BITBOARDS[FileC as usize] | FILE_BITBOARDS[FileD as usize]));
-
-            if (self.occupancy & need_empty == 0) && (need_unattacked & self.attacked == 0) {
-                let mv = mv_create_castle(src_sq, dst_sq);
-                self.moves.push(mv);
+        let king_sq = bb_lsb(king);
+        let their_straights = self.position.their_rooks() | self.position.their_queens();
+
+        for right in rights {
+            if self.position.castle & right == 0 {
+                continue;
             }
-        }
-        if (us == Black) && (rights & BLACK_OO != 0) {
-            let src_sq = bb_lsb(self.position.our_king());
-            let dst_sq = Squares::G8 as u8;
-            let need_empty = RANK_BITBOARDS[Rank8 as usize]
-                & (FILE_BITBOARDS[FileF as usize] | FILE_BITBOARDS[FileG as usize]);
-            let need_unattacked = self.position.our_king() | need_empty;
-
-            if (self.occupancy & need_empty == 0) && (need_unattacked & self.attacked == 0) {
-                let mv = mv_create_castle(src_sq, dst_sq);
-                self.moves.push(mv);
+
+            let rook_sq = self.position.castle_rook(right);
+            let rook = bb_from_sq(rook_sq);
+            let (king_dst, rook_dst) = castle_destinations(right);
+            let king_path = bb_between(king_sq, king_dst) | bb_from_sq(king_dst);
+            let rook_path = bb_between(rook_sq, rook_dst) | bb_from_sq(rook_dst);
+            let need_empty = (king_path | rook_path) & !(king | rook);
+            let need_unattacked = king | king_path;
+
+            if (self.occupancy & need_empty != 0) || (need_unattacked & self.attacked != 0) {
+                continue;
             }
-        }
-        if (us == Black) && (rights & BLACK_OOO != 0) {
-            let src_sq = bb_lsb(self.position.our_king());
-            let dst_sq = Squares::C8 as u8;
-            let need_empty = RANK_BITBOARDS[Rank8 as usize]
-                & (FILE_BITBOARDS[FileB as usize]
-                    | FILE_BITBOARDS[FileC as usize]
-                    | FILE_BITBOARDS[FileD as usize]);
-            let need_unattacked = self.position.our_king()
-                | (RANK_BITBOARDS[Rank8 as usize]
-                    & (FILE_BITBOARDS[FileC as usize] | FILE_BITBOARDS[FileD as usize]));
-
-            if (self.occupancy & need_empty == 0) && (need_unattacked & self.attacked == 0) {
-                let mv = mv_create_castle(src_sq, dst_sq);
-                self.moves.push(mv);
+
+            // In Chess960 the castling rook can be what's shielding the king's
+            // destination from an enemy rook or queen along the back rank.
+            let occ = (self.occupancy & !(king | rook)) | bb_from_sq(rook_dst);
+            if !V::PSEUDO_LEGAL && rook_attacks(bb_from_sq(king_dst), occ) & their_straights != 0 {
+                continue;
             }
+
+            let mv = mv_create_castle(king_sq, rook_sq);
+            self.moves.push(mv);
         }
     }
 
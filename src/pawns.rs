@@ -0,0 +1,155 @@
+use crate::attack::*;
+use crate::bitboard::*;
+use crate::position::Position;
+use crate::square::*;
+use crate::things::*;
+
+// Pawn structure features, as bitboards of the pawns (or files) concerned,
+// indexed by color where it matters.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PawnStructure {
+    pub passed: [u64; NUM_COLORS],    // no enemy pawn can stop or capture it
+    pub isolated: [u64; NUM_COLORS],  // no friendly pawn on an adjacent file
+    pub doubled: [u64; NUM_COLORS],   // a friendly pawn behind it on its file
+    pub backward: [u64; NUM_COLORS],  // can't advance safely or be supported
+    pub connected: [u64; NUM_COLORS], // defended by or beside a friendly pawn
+    pub candidate: [u64; NUM_COLORS], // could become passed by advancing
+    pub open_files: u64,              // files with no pawns at all
+    pub half_open_files: [u64; NUM_COLORS], // only the enemy has pawns there
+}
+
+pub fn north_fill(bb: u64) -> u64 {
+    let mut fill = bb;
+    for _ in 0..7 {
+        fill |= bb_north(fill);
+    }
+    fill
+}
+
+pub fn south_fill(bb: u64) -> u64 {
+    let mut fill = bb;
+    for _ in 0..7 {
+        fill |= bb_south(fill);
+    }
+    fill
+}
+
+// Every square on the files of the given squares.
+pub fn file_fill(bb: u64) -> u64 {
+    north_fill(bb) | south_fill(bb)
+}
+
+// Squares ahead of the given squares, from color's point of view.
+pub fn front_span(bb: u64, color: Color) -> u64 {
+    match color {
+        White => north_fill(bb_north(bb)),
+        Black => south_fill(bb_south(bb)),
+        _ => panic!("bogus color"),
+    }
+}
+
+// Squares pawns could ever attack as they advance.
+pub fn attack_span(pawns: u64, color: Color) -> u64 {
+    let front = front_span(pawns, color);
+    bb_east(front) | bb_west(front)
+}
+
+// One square forward or back, from color's point of view.
+pub fn forward(bb: u64, color: Color) -> u64 {
+    match color {
+        White => bb_north(bb),
+        Black => bb_south(bb),
+        _ => panic!("bogus color"),
+    }
+}
+
+fn behind(bb: u64, color: Color) -> u64 {
+    forward(bb, opposite(color))
+}
+
+fn adjacent_files(bb: u64) -> u64 {
+    let files = file_fill(bb);
+    bb_east(files) | bb_west(files)
+}
+
+impl PawnStructure {
+    pub fn new(pos: &Position) -> PawnStructure {
+        let mut s = PawnStructure::default();
+        let all_files = file_fill(pos.pawns(White) | pos.pawns(Black));
+        s.open_files = !all_files;
+
+        for us in [White, Black] {
+            let them = opposite(us);
+            let ours = pos.pawns(us);
+            let theirs = pos.pawns(them);
+            let u = us as usize;
+
+            // A pawn is passed if no enemy pawn is in front of it, on its own
+            // file or an adjacent one.
+            let stoppers = front_span(theirs, them) | attack_span(theirs, them);
+            s.passed[u] = ours & !stoppers;
+            s.isolated[u] = ours & !adjacent_files(ours);
+            s.doubled[u] = ours & front_span(ours, us);
+            s.connected[u] = ours & (pawn_attacks(ours, us) | bb_east(ours) | bb_west(ours));
+
+            // A backward pawn's stop square is attacked by an enemy pawn, and
+            // none of our pawns can ever advance to defend it.
+            let stops = forward(ours, us);
+            let backward_stops = stops & pawn_attacks(theirs, them) & !attack_span(ours, us);
+            s.backward[u] = ours & behind(backward_stops, us);
+
+            s.half_open_files[u] = !file_fill(ours) & file_fill(theirs);
+
+            // A candidate has an open file ahead, and at least as many
+            // friendly pawns level with or behind it on the adjacent files
+            // as there are enemy pawns ahead of it there.
+            let mut open = ours & !s.passed[u] & !front_span(theirs, them);
+            while open != 0 {
+                let sq = bb_pop(&mut open);
+                let pawn = bb_from_sq(sq);
+                let adjacent = adjacent_files(pawn);
+                let ahead = front_span(RANK_BITBOARDS[rank_of(sq) as usize], us);
+                let sentries = bb_popcnt(theirs & adjacent & ahead);
+                let helpers = bb_popcnt(ours & adjacent & !ahead);
+                if helpers >= sentries {
+                    s.candidate[u] |= pawn;
+                }
+            }
+        }
+        s
+    }
+}
+
+// A small cache of pawn structures, keyed by a hash of the pawns alone.  Pawn
+// structure changes far less often than the rest of the position, so nearly
+// every lookup during a search hits.
+pub struct PawnTable {
+    entries: Vec<(u64, PawnStructure)>,
+}
+
+impl PawnTable {
+    // The number of entries is rounded up to a power of two.
+    pub fn new(entries: usize) -> PawnTable {
+        let entries = entries.next_power_of_two();
+        PawnTable {
+            entries: vec![(0, PawnStructure::default()); entries],
+        }
+    }
+
+    pub fn probe(&mut self, pos: &Position) -> PawnStructure {
+        let key = pos.pawn_key();
+        let index = key as usize & (self.entries.len() - 1);
+        let entry = &mut self.entries[index];
+        // Empty entries have a zero key, as does a board with no pawns.
+        if entry.0 != key || key == 0 {
+            *entry = (key, PawnStructure::new(pos));
+        }
+        entry.1
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new(1 << 14)
+    }
+}
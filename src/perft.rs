@@ -0,0 +1,57 @@
+use crate::moves::*;
+use crate::position::Position;
+use crate::variant::*;
+use std::time::Instant;
+
+pub fn perft<V: Variant>(depth: u32, pos: &mut Position) -> usize {
+    let mut move_generator = MoveGen::<V>::for_variant(pos);
+    move_generator.gen_legal_moves();
+
+    if depth == 1 {
+        return move_generator.moves.len();
+    }
+
+    let mut nodes: usize = 0;
+    for mv in move_generator.moves {
+        let undo = pos.make_variant_move::<V>(mv);
+        nodes += perft::<V>(depth - 1, pos);
+        pos.unmake_variant_move::<V>(undo);
+    }
+    nodes
+}
+
+// Like perft(), listing the count below each move.
+pub fn divide<V: Variant>(depth: u32, pos: &mut Position) -> usize {
+    let nodes = divide_to::<V, _>(depth, pos, |line| println!("{}", line));
+    println!();
+    nodes
+}
+
+// The same, handing each line of the listing to report.
+pub fn divide_to<V: Variant, F: FnMut(&str)>(depth: u32, pos: &mut Position, mut report: F) -> usize {
+    let mut total_nodes: usize = 0;
+    let mut move_generator = MoveGen::<V>::for_variant(pos);
+    move_generator.gen_legal_moves();
+
+    report(&format!("node count, depth=={}", depth));
+    let start = Instant::now();
+    if depth == 1 {
+        total_nodes = move_generator.moves.len();
+    } else if depth > 1 {
+        for mv in move_generator.moves {
+            let undo = pos.make_variant_move::<V>(mv);
+            let nodes = perft::<V>(depth - 1, pos);
+            total_nodes += nodes;
+            pos.unmake_variant_move::<V>(undo);
+
+            report(&format!("{}:  {}", mv_to_str(mv, pos.chess960), nodes));
+        }
+    }
+    let usec = 1 + start.elapsed().as_micros();
+    let sec = usec as f64 / 1000000.;
+    let milli = usec as f64 / 1000.;
+    let knps = (total_nodes as f64 / sec) / 1000.;
+
+    report(&format!("{} nodes in {} ms; {} knps", total_nodes, milli, knps as u64));
+    total_nodes
+}
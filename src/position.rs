@@ -1,15 +1,162 @@
 use crate::attack::*;
 use crate::bitboard::*;
+use crate::eval::*;
 use crate::moves::*;
 use crate::square::*;
 use crate::things::*;
+use crate::variant::*;
+use crate::zobrist::*;
+use std::fmt;
 
 const EP_OFFSETS: [i16; NUM_COLORS] = [8, -8];
-const CASTLE_RIGHTS: [u8; NUM_SQUARES] = [
-    13, 15, 15, 15, 12, 15, 15, 14, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
-    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
-    15, 15, 15, 15, 15, 15, 15, 15, 7, 15, 15, 15, 3, 15, 15, 11,
-];
+const ALL_CASTLE_RIGHTS: u8 = WHITE_OO | WHITE_OOO | BLACK_OO | BLACK_OOO;
+
+// Index of a single castle right into the per-right tables.
+fn castle_index(right: u8) -> usize {
+    right.trailing_zeros() as usize
+}
+
+// Which castle right a (king, rook) pair belongs to.  In Chess960 the king
+// and rook can start anywhere on the back rank, but OO is always with the
+// rook on the H side of the king.
+pub fn castle_right(color: Color, king_sq: u8, rook_sq: u8) -> u8 {
+    let kingside = file_of(rook_sq) > file_of(king_sq);
+    match (color, kingside) {
+        (White, true) => WHITE_OO,
+        (White, false) => WHITE_OOO,
+        (Black, true) => BLACK_OO,
+        (Black, false) => BLACK_OOO,
+        _ => panic!("bogus color"),
+    }
+}
+
+// Where the king and rook end up after castling.  These are the same squares
+// in standard chess and Chess960.
+pub fn castle_destinations(right: u8) -> (u8, u8) {
+    match right {
+        WHITE_OO => (Squares::G1 as u8, Squares::F1 as u8),
+        WHITE_OOO => (Squares::C1 as u8, Squares::D1 as u8),
+        BLACK_OO => (Squares::G8 as u8, Squares::F8 as u8),
+        BLACK_OOO => (Squares::C8 as u8, Squares::D8 as u8),
+        _ => panic!("bogus castle right"),
+    }
+}
+
+// Returned when asked to play a move that isn't legal in the position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IllegalMove(pub u16);
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let src = mv_get_src(self.0);
+        let dst = mv_get_dst(self.0);
+        write!(f, "illegal move {}{}", sq_to_str(src), sq_to_str(dst))
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+// Returned when asked to set up a position from a FEN that can't be read, or
+// that describes a board the engine can't play from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IllegalFen(pub &'static str);
+
+impl fmt::Display for IllegalFen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "illegal FEN: {}", self.0)
+    }
+}
+
+impl std::error::Error for IllegalFen {}
+
+// Checks everything from_variant_fen() takes on trust: the board, side to
+// move, castling rights, en passant square and move counters.
+fn check_fen(fields: &[&str]) -> Result<(), IllegalFen> {
+    if fields.len() < 4 {
+        return Err(IllegalFen("expected board, side, castling and en passant fields"));
+    }
+
+    let (board, _) = split_pocket(fields[0]);
+    let ranks: Vec<&str> = board.split('/').collect();
+    if ranks.len() != NUM_RANKS {
+        return Err(IllegalFen("expected eight ranks"));
+    }
+    let mut squares = ['.'; NUM_SQUARES];
+    for (i, text) in ranks.iter().enumerate() {
+        let rank = (NUM_RANKS - 1 - i) as u8;
+        let mut file = 0;
+        for c in text.chars() {
+            match c {
+                '1'..='8' => file += c as u8 - b'0',
+                '~' if file > 0 => {}
+                'P' | 'N' | 'B' | 'R' | 'Q' | 'K' | 'p' | 'n' | 'b' | 'r' | 'q' | 'k' if file < NUM_FILES as u8 => {
+                    squares[make_sq(rank, file) as usize] = c;
+                    file += 1;
+                }
+                _ => return Err(IllegalFen("bad rank")),
+            }
+        }
+        if file != NUM_FILES as u8 {
+            return Err(IllegalFen("bad rank"));
+        }
+    }
+
+    if !matches!(fields[1], "w" | "b" | "W" | "B") {
+        return Err(IllegalFen("side to move must be w or b"));
+    }
+
+    // Each right needs the only king of its color on its back rank, and a
+    // rook to castle with on the side the right is for.
+    if fields[2] != "-" {
+        for c in fields[2].chars() {
+            let (king, rook, back_rank) = if c.is_ascii_uppercase() {
+                ('K', 'R', Rank1 as u8)
+            } else {
+                ('k', 'r', Rank8 as u8)
+            };
+            let on_back_rank = |piece| (0..NUM_FILES as u8).filter(move |&file| squares[make_sq(back_rank, file) as usize] == piece);
+            let kings = squares.iter().filter(|&&piece| piece == king).count();
+            let (Some(king_file), 1) = (on_back_rank(king).next(), kings) else {
+                return Err(IllegalFen("castling without a king on the back rank"));
+            };
+            let has_rook = match c.to_ascii_lowercase() {
+                'k' => on_back_rank(rook).any(|file| file > king_file),
+                'q' => on_back_rank(rook).any(|file| file < king_file),
+                'a'..='h' => on_back_rank(rook).any(|file| file == c.to_ascii_lowercase() as u8 - b'a'),
+                _ => return Err(IllegalFen("bad castling rights")),
+            };
+            if !has_rook {
+                return Err(IllegalFen("castling without a rook"));
+            }
+        }
+    }
+
+    let ep = fields[3].as_bytes();
+    if fields[3] != "-" && !(ep.len() == 2 && (b'a'..=b'h').contains(&ep[0]) && (ep[1] == b'3' || ep[1] == b'6')) {
+        return Err(IllegalFen("bad en passant square"));
+    }
+
+    for field in &fields[4..] {
+        if !field.contains('+') && field.parse::<u32>().is_err() {
+            return Err(IllegalFen("bad move counter"));
+        }
+    }
+    Ok(())
+}
+
+// Splits pieces in hand off the board field of a FEN.  They're either
+// bracketed after the board or written as a ninth rank.
+fn split_pocket(board: &str) -> (&str, Option<&str>) {
+    if let Some(open) = board.find('[') {
+        let pocket = board[open + 1..].trim_end_matches(']');
+        (&board[..open], Some(pocket))
+    } else if board.matches('/').count() == NUM_RANKS {
+        let slash = board.rfind('/').unwrap();
+        (&board[..slash], Some(&board[slash + 1..]))
+    } else {
+        (board, None)
+    }
+}
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct Position {
@@ -23,6 +170,27 @@ pub struct Position {
     pub ep: u8,      // ep square
     half: i32,       // halfmove clock
     full: i32,       // fullmove clock
+
+    pub chess960: bool, // castling moves are written king-takes-rook
+
+    castle_rooks: [u8; NUM_CASTLE_RIGHTS], // rook start square per castle right
+    castle_masks: [u8; NUM_SQUARES],       // rights kept when a square is touched
+
+    pocket: [[u8; NUM_PIECES]; NUM_COLORS], // pieces in hand, by color and piece
+    pub promoted: u64,                      // pieces that were once pawns
+    pub checks: [u8; NUM_COLORS],           // checks given by each color
+
+    psq: Score, // material and piece-square score, white's point of view
+    phase: i32, // game phase, from the non-pawn material on the board
+
+    key: u64,      // Zobrist hash of the pieces on the board
+    pawn_key: u64, // Zobrist hash of the pawns alone
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Position {
@@ -37,6 +205,16 @@ impl Position {
             castle: 0,
             half: 0,
             full: 0,
+            chess960: false,
+            castle_rooks: [NO_SQUARE; NUM_CASTLE_RIGHTS],
+            castle_masks: [ALL_CASTLE_RIGHTS; NUM_SQUARES],
+            pocket: [[0; NUM_PIECES]; NUM_COLORS],
+            promoted: 0,
+            checks: [0; NUM_COLORS],
+            psq: Score::default(),
+            phase: 0,
+            key: 0,
+            pawn_key: 0,
         }
     }
 
@@ -136,6 +314,78 @@ impl Position {
         self.bb_piece[King as usize] & self.bb_color[color as usize]
     }
 
+    pub fn pieces(&self, piece: Piece, color: Color) -> u64 {
+        self.bb_piece[piece as usize] & self.bb_color[color as usize]
+    }
+
+    pub fn color_pieces(&self, color: Color) -> u64 {
+        self.bb_color[color as usize]
+    }
+
+    // Start square of the rook for the given castle right.
+    pub fn castle_rook(&self, right: u8) -> u8 {
+        self.castle_rooks[castle_index(right)]
+    }
+
+    // Called when whatever is on sq moves or disappears.
+    pub fn update_castle_rights(&mut self, sq: u8) {
+        self.castle &= self.castle_masks[sq as usize];
+    }
+
+    fn add_castle_right(&mut self, color: Color, rook_sq: u8) {
+        let king_sq = bb_lsb(self.king(color));
+        let right = castle_right(color, king_sq, rook_sq);
+        let both = if color == White {
+            WHITE_OO | WHITE_OOO
+        } else {
+            BLACK_OO | BLACK_OOO
+        };
+
+        debug_assert!(rank_of(king_sq) == rank_of(rook_sq));
+
+        self.castle |= right;
+        self.castle_rooks[castle_index(right)] = rook_sq;
+
+        // Moving the king loses both rights; moving or losing the rook loses
+        // just this one.
+        self.castle_masks[king_sq as usize] &= !both;
+        self.castle_masks[rook_sq as usize] &= !right;
+    }
+
+    pub fn psq(&self) -> Score {
+        self.psq
+    }
+
+    pub fn phase(&self) -> i32 {
+        self.phase
+    }
+
+    pub fn pawn_key(&self) -> u64 {
+        self.pawn_key
+    }
+
+    // Zobrist hash of the position.  Only the pieces are hashed as they come
+    // and go; the rest is cheap enough to mix in on demand, and doing it here
+    // keeps make/unmake and null moves from having to update it.
+    pub fn hash(&self) -> u64 {
+        let mut hash = self.key ^ castle_key(self.castle);
+        if self.side == Black {
+            hash ^= side_key();
+        }
+        if self.ep != NO_SQUARE {
+            hash ^= ep_key(self.ep);
+        }
+        hash
+    }
+
+    pub fn halfmove_clock(&self) -> i32 {
+        self.half
+    }
+
+    pub fn fullmove_number(&self) -> i32 {
+        self.full
+    }
+
     pub fn piece_on(&self, sq: u8) -> Piece {
         self.piece_sq[sq as usize]
     }
@@ -144,7 +394,7 @@ impl Position {
         self.color_sq[sq as usize]
     }
 
-    fn put_piece(&mut self, sq: u8, piece: Piece, color: Color) {
+    pub fn put_piece(&mut self, sq: u8, piece: Piece, color: Color) {
         let s_idx = sq as usize;
         let p_idx = piece as usize;
         let c_idx = color as usize;
@@ -156,16 +406,30 @@ impl Position {
         self.color_sq[s_idx] = color;
         self.bb_piece[p_idx] |= bb_from_sq(sq);
         self.bb_color[c_idx] |= bb_from_sq(sq);
+        self.psq += psq_score(piece, color, sq);
+        self.phase += PHASE_VALUES[p_idx];
+        self.key ^= piece_key(piece, color, sq);
+        if piece == Pawn {
+            self.pawn_key ^= piece_key(piece, color, sq);
+        }
     }
 
-    fn clear_sq(&mut self, sq: u8) {
+    pub fn clear_sq(&mut self, sq: u8) {
+        let piece = self.piece_on(sq);
+        let color = self.color_on(sq);
         let s_idx = sq as usize;
-        let p_idx = self.piece_on(sq) as usize;
-        let c_idx = self.color_on(sq) as usize;
+        let p_idx = piece as usize;
+        let c_idx = color as usize;
         self.piece_sq[s_idx] = NoPiece;
         self.color_sq[s_idx] = NoColor;
         self.bb_piece[p_idx] &= !bb_from_sq(sq);
         self.bb_color[c_idx] &= !bb_from_sq(sq);
+        self.psq -= psq_score(piece, color, sq);
+        self.phase -= PHASE_VALUES[p_idx];
+        self.key ^= piece_key(piece, color, sq);
+        if piece == Pawn {
+            self.pawn_key ^= piece_key(piece, color, sq);
+        }
     }
 
     fn clear(&mut self) {
@@ -173,18 +437,57 @@ impl Position {
         self.bb_color = [0; NUM_COLORS];
         self.piece_sq = [NoPiece; NUM_SQUARES];
         self.color_sq = [NoColor; NUM_SQUARES];
+        self.pocket = [[0; NUM_PIECES]; NUM_COLORS];
+        self.promoted = 0;
+        self.checks = [0; NUM_COLORS];
+        self.psq = Score::default();
+        self.phase = 0;
+        self.key = 0;
+        self.pawn_key = 0;
+    }
+
+    pub fn pocket_count(&self, color: Color, piece: Piece) -> u8 {
+        self.pocket[color as usize][piece as usize]
+    }
+
+    pub fn add_to_pocket(&mut self, color: Color, piece: Piece) {
+        self.pocket[color as usize][piece as usize] += 1;
+    }
+
+    pub fn remove_from_pocket(&mut self, color: Color, piece: Piece) {
+        debug_assert!(self.pocket_count(color, piece) > 0);
+        self.pocket[color as usize][piece as usize] -= 1;
     }
 
     pub fn from_fen(&mut self, fen: &str) {
+        self.from_variant_fen::<Standard>(fen);
+    }
+
+    // Besides standard FEN, this accepts the extensions variants use: pieces
+    // in hand after the board, either bracketed ("[QNp]") or as a ninth rank,
+    // and check counters either before ("3+3") or after ("+2+1") the move
+    // counters.  The move counters themselves may be left off.
+    pub fn from_variant_fen<V: Variant>(&mut self, fen: &str) {
         let mut sq = Squares::A8 as u8;
         let mut rank = Rank8 as u8;
-        let split: Vec<&str> = fen.split(" ").collect();
-        let board = split[0].chars();
+        let split: Vec<&str> = fen.split_whitespace().collect();
+        let (board, pocket) = split_pocket(split[0]);
+        let board = board.chars();
         let side = split[1].as_bytes()[0].to_ascii_lowercase() as char; // ?!
         let castling = split[2].chars();
         let ep = split[3].as_bytes();
-        let half = split[4].parse::<i32>().unwrap(); // ?!
-        let full = split[5].parse::<i32>().unwrap();
+
+        let mut counters: Vec<i32> = Vec::new();
+        let mut checks = None;
+        for field in split.iter().skip(4) {
+            if field.contains('+') {
+                checks = Some(*field);
+            } else {
+                counters.push(field.parse::<i32>().unwrap()); // ?!
+            }
+        }
+        let half = counters.first().copied().unwrap_or(0);
+        let full = counters.get(1).copied().unwrap_or(1);
 
         self.clear();
 
@@ -198,24 +501,57 @@ impl Position {
                     None => panic!("bogus input"),
                 }
             } else if c == '/' {
-                rank = rank - 1;
-                sq = make_sq(rank as u8, FileA as u8);
+                rank -= 1;
+                sq = make_sq(rank, FileA as u8);
+            } else if c == '~' {
+                // The piece before this was promoted from a pawn.
+                self.promoted |= bb_from_sq(sq - 1);
             } else {
                 let piece = piece_from_char(c);
                 let color = color_from_char(c);
                 self.put_piece(sq, piece, color);
-                sq = sq + 1;
+                sq += 1;
             }
         }
 
+        // Castling rights may be given as KQkq, as in standard FEN and X-FEN,
+        // or as the files of the castling rooks, as in Shredder-FEN.  K and Q
+        // refer to the outermost rook on that side of the king.
         self.castle = 0;
+        self.castle_rooks = [NO_SQUARE; NUM_CASTLE_RIGHTS];
+        self.castle_masks = [ALL_CASTLE_RIGHTS; NUM_SQUARES];
+        self.chess960 = false;
         for c in castling {
-            match c {
-                'K' => self.castle |= WHITE_OO,
-                'Q' => self.castle |= WHITE_OOO,
-                'k' => self.castle |= BLACK_OO,
-                'q' => self.castle |= BLACK_OOO,
-                _ => break,
+            if c == '-' {
+                break;
+            }
+            let color = color_from_char(c);
+            let back_rank = if color == White { Rank1 } else { Rank8 };
+            let back_rank_rooks = self.rooks(color) & RANK_BITBOARDS[back_rank as usize];
+            let king_sq = bb_lsb(self.king(color));
+            let rook_sq = match c.to_ascii_lowercase() {
+                'k' => bb_msb(back_rank_rooks),
+                'q' => bb_lsb(back_rank_rooks),
+                'a'..='h' => {
+                    self.chess960 = true;
+                    make_sq(back_rank as u8, c.to_ascii_lowercase() as u8 - b'a')
+                }
+                _ => panic!("bogus castling rights"),
+            };
+            debug_assert!(back_rank_rooks & bb_from_sq(rook_sq) != 0);
+            debug_assert!(rank_of(king_sq) == back_rank as u8);
+            self.add_castle_right(color, rook_sq);
+        }
+
+        // Castling from anywhere but the standard squares means Chess960.
+        for right in [WHITE_OO, WHITE_OOO, BLACK_OO, BLACK_OOO] {
+            if self.castle & right != 0 {
+                let color = if right & (WHITE_OO | WHITE_OOO) != 0 { White } else { Black };
+                let king_file = file_of(bb_lsb(self.king(color)));
+                let rook_file = file_of(self.castle_rook(right));
+                if king_file != FileE as u8 || (rook_file != FileA as u8 && rook_file != FileH as u8) {
+                    self.chess960 = true;
+                }
             }
         }
 
@@ -225,17 +561,56 @@ impl Position {
             _ => panic!("weird side"),
         }
 
+        self.ep = NO_SQUARE;
         if ep[0] as char != '-' {
-            let ep_file = ep[0] - ('a' as u8);
-            let ep_rank = ep[1] - ('1' as u8);
+            let ep_file = ep[0] - b'a';
+            let ep_rank = ep[1] - b'1';
             self.ep = make_sq(ep_rank, ep_file);
         }
 
         self.half = half;
         self.full = full;
+
+        V::parse_fen_extensions(self, &FenExtensions { pocket, checks });
+    }
+
+    // Like from_variant_fen(), but for FENs from outside, which may be
+    // anything: one that can't be read leaves the position as it was.
+    pub fn try_from_variant_fen<V: Variant>(&mut self, fen: &str) -> Result<(), IllegalFen> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        check_fen(&fields)?;
+        let (_, pocket) = split_pocket(fields[0]);
+        let checks = fields.iter().skip(4).rev().copied().find(|field| field.contains('+'));
+        V::check_fen_extensions(&FenExtensions { pocket, checks })?;
+        self.from_variant_fen::<V>(fen);
+        Ok(())
+    }
+
+    // The same for standard chess, which also needs one king a side, no
+    // pawns on the back ranks, and the side that just moved not in check.
+    pub fn try_from_fen(&mut self, fen: &str) -> Result<(), IllegalFen> {
+        let mut pos = *self;
+        pos.try_from_variant_fen::<Standard>(fen)?;
+        if bb_popcnt(pos.king(White)) != 1 || bb_popcnt(pos.king(Black)) != 1 {
+            return Err(IllegalFen("each side needs one king"));
+        }
+        let back_ranks = RANK_BITBOARDS[Rank1 as usize] | RANK_BITBOARDS[Rank8 as usize];
+        if (pos.pawns(White) | pos.pawns(Black)) & back_ranks != 0 {
+            return Err(IllegalFen("pawn on a back rank"));
+        }
+        let their_king = bb_lsb(pos.king(pos.enemy()));
+        if pos.attackers_to(their_king, pos.occupancy()) & pos.color_pieces(pos.side) != 0 {
+            return Err(IllegalFen("the side not to move is in check"));
+        }
+        *self = pos;
+        Ok(())
     }
 
     pub fn to_fen(&self) -> String {
+        self.to_variant_fen::<Standard>()
+    }
+
+    pub fn to_variant_fen<V: Variant>(&self) -> String {
         let mut s = String::new();
         for rank in (0..(NUM_RANKS as u8)).rev() {
             let mut empty: u8 = 0;
@@ -253,6 +628,9 @@ impl Position {
                         empty = 0;
                     }
                     s.push(c.unwrap());
+                    if self.promoted & bb_from_sq(sq) != 0 {
+                        s.push('~');
+                    }
                 }
             }
             if empty > 0 {
@@ -262,6 +640,7 @@ impl Position {
                 s.push('/');
             }
         }
+        s.push_str(&V::fen_board_suffix(self));
         match self.side {
             White => s.push_str(" w "),
             Black => s.push_str(" b "),
@@ -272,17 +651,10 @@ impl Position {
         if self.castle == 0 {
             s.push('-');
         } else {
-            if self.castle & WHITE_OO != 0 {
-                s.push('K');
-            }
-            if self.castle & WHITE_OOO != 0 {
-                s.push('Q');
-            }
-            if self.castle & BLACK_OO != 0 {
-                s.push('k');
-            }
-            if self.castle & BLACK_OOO != 0 {
-                s.push('q');
+            for right in [WHITE_OO, WHITE_OOO, BLACK_OO, BLACK_OOO] {
+                if self.castle & right != 0 {
+                    s.push(self.castle_char(right));
+                }
             }
         }
         s.push(' ');
@@ -291,19 +663,58 @@ impl Position {
         s.push_str(&self.half.to_string());
         s.push(' ');
         s.push_str(&self.full.to_string());
+        s.push_str(&V::fen_suffix(self));
         s
     }
 
+    // Castling right as written in FEN.  Chess960 positions use X-FEN, which
+    // only names the rook's file when it isn't the outermost one.
+    fn castle_char(&self, right: u8) -> char {
+        let rook_sq = self.castle_rook(right);
+        let (color, kingside) = match right {
+            WHITE_OO => (White, true),
+            WHITE_OOO => (White, false),
+            BLACK_OO => (Black, true),
+            BLACK_OOO => (Black, false),
+            _ => panic!("bogus castle right"),
+        };
+        let back_rank_rooks = self.rooks(color) & RANK_BITBOARDS[rank_of(rook_sq) as usize];
+        let outermost = if kingside {
+            bb_msb(back_rank_rooks)
+        } else {
+            bb_lsb(back_rank_rooks)
+        };
+        let c = if !self.chess960 || rook_sq == outermost {
+            if kingside {
+                'k'
+            } else {
+                'q'
+            }
+        } else {
+            (b'a' + file_of(rook_sq)) as char
+        };
+        if color == White {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        }
+    }
+
     pub fn debug(&self) {
+        println!("{}", self.board_to_str());
+    }
+
+    // The board drawn in ASCII, followed by the FEN.
+    pub fn board_to_str(&self) -> String {
         let mut s = String::new();
         s.push_str("\n     A   B   C   D   E   F   G   H\n");
         s.push_str("   +---+---+---+---+---+---+---+---+\n");
 
         for rank in (0..NUM_RANKS).rev() {
             let rank = rank as u8; // clumsy
-            s.push_str(" ");
+            s.push(' ');
             s.push_str(&(rank + 1).to_string());
-            s.push_str(" ");
+            s.push(' ');
             for file in 0..NUM_FILES {
                 let file = file as u8;
                 let sq = make_sq(rank, file);
@@ -311,32 +722,62 @@ impl Position {
                 let color = self.color_on(sq);
                 s.push_str("| ");
                 s.push_str(&piece_to_str(piece, color));
-                s.push_str(" ");
+                s.push(' ');
             }
             s.push_str("|\n   +---+---+---+---+---+---+---+---+\n");
         }
         s.push_str(&self.to_fen());
-        println!("{}", s);
+        s
     }
 
     pub fn make_move(&mut self, mv: u16) -> Undo {
+        self.make_variant_move::<Standard>(mv)
+    }
+
+    pub fn unmake_move(&mut self, undo: Undo) {
+        self.unmake_variant_move::<Standard>(undo);
+    }
+
+    pub fn make_variant_move<V: Variant>(&mut self, mv: u16) -> Undo {
         let src = mv_get_src(mv);
         let dst = mv_get_dst(mv);
         let kind = mv_get_kind(mv);
         let moved_piece = self.piece_on(src);
         let moved_color = self.color_on(src);
-        let captured_piece = self.piece_on(dst);
+        let captured_piece = if kind == MOVE_CASTLE {
+            NoPiece // the king "captures" its own rook
+        } else {
+            self.piece_on(dst)
+        };
         let is_capture = captured_piece != NoPiece;
         let is_pawn_mv = moved_piece == Pawn;
         let enemy = self.enemy();
-        let undo = Undo {
-            mv: mv,
+        let mut undo = Undo {
+            mv,
             captured: captured_piece,
             castle: self.castle,
             half: self.half,
             ep: self.ep,
+            promoted: self.promoted,
+            exploded: 0,
         };
 
+        if kind == MOVE_DROP {
+            let piece = mv_get_drop_piece(mv);
+            self.remove_from_pocket(self.side, piece);
+            self.put_piece(dst, piece, self.side);
+            if piece == Pawn {
+                self.half = 0;
+            } else {
+                self.half += 1;
+            }
+            self.full += self.side as i32;
+            self.ep = NO_SQUARE;
+            self.side = enemy;
+            V::after_move(self, &mut undo);
+            return undo;
+        }
+
         debug_assert!(moved_color == self.side);
 
         if is_capture || is_pawn_mv {
@@ -362,30 +803,19 @@ impl Position {
                 self.put_piece(dst, Pawn, self.side);
             }
             MOVE_CASTLE => {
-                // The move's src and dst squares are for the king; if king
-                // moves to the right then it's OO, else OOO.  From this, the
-                // rook location is determined.
-                let src_file = file_of(src);
-                let dst_file = file_of(dst);
-                let rook_src_file = if src_file < dst_file { FileH } else { FileA } as u8;
-                let rook_dst_file = if rook_src_file == FileH as u8 {
-                    FileF
-                } else {
-                    FileD
-                } as u8;
-                let rank = if self.side == White { Rank1 } else { Rank8 } as u8;
-                let rook_src_sq = make_sq(rank, rook_src_file);
-                let rook_dst_sq = make_sq(rank, rook_dst_file);
+                // Castling is encoded as the king capturing its own rook, which
+                // works wherever the two start out (Chess960).  Both end up on
+                // the usual squares.
+                let right = castle_right(self.side, src, dst);
+                let (king_dst, rook_dst) = castle_destinations(right);
 
                 debug_assert!(moved_piece == King);
-                debug_assert!(file_of(src) == FileE as u8);
-                debug_assert!(self.piece_on(rook_src_sq) == Rook);
-                debug_assert!(self.color_on(rook_src_sq) == self.side);
-                debug_assert!(self.piece_on(rook_dst_sq) == NoPiece);
-
-                self.clear_sq(rook_src_sq);
-                self.put_piece(dst, King, self.side);
-                self.put_piece(rook_dst_sq, Rook, self.side);
+                debug_assert!(self.piece_on(dst) == Rook);
+                debug_assert!(self.color_on(dst) == self.side);
+
+                self.clear_sq(dst);
+                self.put_piece(king_dst, King, self.side);
+                self.put_piece(rook_dst, Rook, self.side);
             }
             MOVE_PROMO => {
                 let promoted_piece = mv_get_promo_piece(mv);
@@ -405,19 +835,21 @@ impl Position {
             _ => panic!("Unknown move kind"),
         }
 
-        self.castle &= CASTLE_RIGHTS[src as usize];
-        self.castle &= CASTLE_RIGHTS[dst as usize];
+        self.update_castle_rights(src);
+        self.update_castle_rights(dst);
         self.side = enemy;
+        V::after_move(self, &mut undo);
         undo
     }
 
-    pub fn unmake_move(&mut self, undo: Undo) {
+    pub fn unmake_variant_move<V: Variant>(&mut self, undo: Undo) {
+        V::before_unmake(self, &undo);
+
         // src and dst are from the perspective of the player before the move
         // was made
         let src = mv_get_src(undo.mv);
         let dst = mv_get_dst(undo.mv);
         let kind = mv_get_kind(undo.mv);
-        let piece = self.piece_on(dst);
         let captured = undo.captured;
         let enemy = self.side;
 
@@ -427,11 +859,28 @@ impl Position {
         // self.full -= self.side;
         self.full -= if self.side == Black { 1 } else { 0 }; // CLUMSY
 
-        self.clear_sq(dst);
-        self.put_piece(src, piece, self.side);
-        if captured != NoPiece {
-            // self.put_piece(dst, captured, self.side ^ 1);
-            self.put_piece(dst, captured, enemy);
+        if kind == MOVE_DROP {
+            // Back into the pocket it goes.
+            let piece = mv_get_drop_piece(undo.mv);
+            self.clear_sq(dst);
+            self.add_to_pocket(self.side, piece);
+        } else if kind == MOVE_CASTLE {
+            // The king and rook may have landed on each other's start squares,
+            // so lift both before putting either back.
+            let right = castle_right(self.side, src, dst);
+            let (king_dst, rook_dst) = castle_destinations(right);
+            self.clear_sq(king_dst);
+            self.clear_sq(rook_dst);
+            self.put_piece(src, King, self.side);
+            self.put_piece(dst, Rook, self.side);
+        } else {
+            let piece = self.piece_on(dst);
+            self.clear_sq(dst);
+            self.put_piece(src, piece, self.side);
+            if captured != NoPiece {
+                // self.put_piece(dst, captured, self.side ^ 1);
+                self.put_piece(dst, captured, enemy);
+            }
         }
 
         match kind {
@@ -440,30 +889,12 @@ impl Position {
                 let sq = (undo.ep as i16 - EP_OFFSETS[self.side as usize]) as u8; // CLUMSY
                 self.put_piece(sq, Pawn, self.enemy());
             }
-            MOVE_CASTLE => {
-                // Put the rook back.  The king is back already.
-                let rank = if self.side == White { Rank1 } else { Rank8 } as u8;
-                let rook_src_file = if file_of(dst) > file_of(src) {
-                    FileH
-                } else {
-                    FileA
-                } as u8;
-                let rook_dst_file = if rook_src_file == FileH as u8 {
-                    FileF
-                } else {
-                    FileD
-                } as u8;
-                let rook_dst_sq = make_sq(rank, rook_dst_file);
-                let rook_src_sq = make_sq(rank, rook_src_file);
-                self.clear_sq(rook_dst_sq);
-                self.put_piece(rook_src_sq, Rook, self.side);
-            }
             MOVE_PROMO => {
                 // Transform the promoted piece back to a pawn.
                 self.clear_sq(src);
                 self.put_piece(src, Pawn, self.side);
             }
-            MOVE_NORMAL => {}
+            MOVE_NORMAL | MOVE_CASTLE | MOVE_DROP => {}
             _ => panic!("Unknown move kind"),
         }
         self.castle = undo.castle;
@@ -471,8 +902,75 @@ impl Position {
         self.ep = undo.ep;
     }
 
+    // Copy-make: returns the position after mv, leaving this one untouched.
+    // The move is assumed to be legal.
+    pub fn play(&self, mv: u16) -> Position {
+        self.play_variant::<Standard>(mv)
+    }
+
+    pub fn play_variant<V: Variant>(&self, mv: u16) -> Position {
+        let mut next = *self;
+        next.make_variant_move::<V>(mv);
+        next
+    }
+
+    // The legal move written in coordinate notation as s, if there is one.
+    pub fn parse_move(&self, s: &str) -> Option<u16> {
+        let mut move_generator = MoveGen::new(self);
+        move_generator.gen_legal_moves();
+        let moves = move_generator.moves;
+        moves.iter().copied().find(|&mv| mv_to_str(mv, self.chess960) == s)
+    }
+
+    // Like play(), but checks the move against the legal moves first.
+    pub fn try_play(&self, mv: u16) -> Result<Position, IllegalMove> {
+        let mut move_generator = MoveGen::new(self);
+        move_generator.gen_legal_moves();
+        if move_generator.moves.contains(&mv) {
+            Ok(self.play(mv))
+        } else {
+            Err(IllegalMove(mv))
+        }
+    }
+
+    // Pass the turn to the other side without moving anything.  This isn't a
+    // legal chess move, but search uses it to detect threats.  Passing while
+    // in check would let the enemy capture our king, so it's not allowed.
+    pub fn make_null_move(&mut self) -> Undo {
+        debug_assert!(self.calc_checkers() == 0);
+
+        let undo = Undo {
+            mv: NULL_MOVE,
+            captured: NoPiece,
+            castle: self.castle,
+            half: self.half,
+            ep: self.ep,
+            promoted: self.promoted,
+            exploded: 0,
+        };
+
+        self.half += 1;
+        self.full += self.side as i32;
+        self.ep = NO_SQUARE;
+        self.side = self.enemy();
+        undo
+    }
+
+    pub fn unmake_null_move(&mut self, undo: Undo) {
+        debug_assert!(undo.mv == NULL_MOVE);
+
+        self.side = self.enemy();
+        self.full -= self.side as i32;
+        self.half = undo.half;
+        self.ep = undo.ep;
+    }
+
     pub fn calc_checkers(&self) -> u64 {
         let king = self.our_king();
+        if king == 0 {
+            // The horde has no king to check.
+            return 0;
+        }
         let king_sq = bb_lsb(king);
         let their_rooks = self.their_rooks();
         let their_bishops = self.their_bishops();
@@ -496,11 +994,12 @@ impl Position {
         let their_diagonals = their_queens | their_bishops;
         let their_straights = their_queens | their_rooks;
 
-        if their_diagonals | their_straights != 0 {
+        // Without a king (the horde) there's nothing to be pinned against.
+        if self.our_king() != 0 && their_diagonals | their_straights != 0 {
             // Enemy has slider pieces on the board.   Need to check to see if
             // any of them are pinning any of our pieces to our king.
             let our_king = self.our_king();
-            let king_sq = bb_lsb(our_king) as u8;
+            let king_sq = bb_lsb(our_king);
             debug_assert!(bb_popcnt(our_king) == 1);
 
             // Conceptually, put a queen where our king is on an empty board.
@@ -533,4 +1032,71 @@ impl Position {
         }
         pinned
     }
+    // All pieces of either color attacking sq, given the occupancy.  Passing in
+    // an occupancy other than the board's lets callers see through pieces.
+    pub fn attackers_to(&self, sq: u8, occupancy: u64) -> u64 {
+        let bb = bb_from_sq(sq);
+        let diagonals = self.bb_piece[Bishop as usize] | self.bb_piece[Queen as usize];
+        let straights = self.bb_piece[Rook as usize] | self.bb_piece[Queen as usize];
+        let mut attackers: u64 = 0;
+
+        attackers |= pawn_attacks(bb, Black) & self.pawns(White);
+        attackers |= pawn_attacks(bb, White) & self.pawns(Black);
+        attackers |= knight_attacks_from(sq) & self.bb_piece[Knight as usize];
+        attackers |= king_attacks_from(sq) & self.bb_piece[King as usize];
+        attackers |= bishop_attacks(bb, occupancy) & diagonals;
+        attackers |= rook_attacks(bb, occupancy) & straights;
+        attackers & occupancy
+    }
+    // Would playing mv put the enemy king in check?  Works out which of our
+    // pieces would attack the king given the occupancy after the move, which
+    // catches discovered checks as well as direct ones.
+    pub fn gives_check(&self, mv: u16) -> bool {
+        let their_king = self.their_king();
+        if their_king == 0 {
+            return false;
+        }
+        let king_sq = bb_lsb(their_king);
+        let src = mv_get_src(mv);
+        let dst = mv_get_dst(mv);
+        let src_bb = bb_from_sq(src);
+        let dst_bb = bb_from_sq(dst);
+        let mut occ = self.occupancy();
+        let mut ours = [0; NUM_PIECES];
+        for piece in [Pawn, Knight, Bishop, Rook, Queen] {
+            ours[piece as usize] = self.pieces(piece, self.side);
+        }
+
+        match mv_get_kind(mv) {
+            MOVE_DROP => {
+                ours[mv_get_drop_piece(mv) as usize] |= dst_bb;
+                occ |= dst_bb;
+            }
+            MOVE_CASTLE => {
+                let (king_dst, rook_dst) = castle_destinations(castle_right(self.side, src, dst));
+                ours[Rook as usize] = (ours[Rook as usize] & !dst_bb) | bb_from_sq(rook_dst);
+                occ = (occ & !(src_bb | dst_bb)) | bb_from_sq(king_dst) | bb_from_sq(rook_dst);
+            }
+            kind => {
+                let piece = self.piece_on(src);
+                let moved = if kind == MOVE_PROMO { mv_get_promo_piece(mv) } else { piece };
+                ours[piece as usize] &= !src_bb;
+                ours[moved as usize] |= dst_bb;
+                occ = (occ & !src_bb) | dst_bb;
+                if kind == MOVE_ENPASSANT {
+                    let capture_sq = (dst as i16 - EP_OFFSETS[self.side as usize]) as u8;
+                    occ &= !bb_from_sq(capture_sq);
+                }
+            }
+        }
+
+        let diagonals = ours[Bishop as usize] | ours[Queen as usize];
+        let straights = ours[Rook as usize] | ours[Queen as usize];
+        let mut attackers: u64 = 0;
+        attackers |= pawn_attacks(their_king, self.enemy()) & ours[Pawn as usize];
+        attackers |= knight_attacks_from(king_sq) & ours[Knight as usize];
+        attackers |= bishop_attacks(their_king, occ) & diagonals;
+        attackers |= rook_attacks(their_king, occ) & straights;
+        attackers != 0
+    }
 }
@@ -0,0 +1,50 @@
+use crate::attack::*;
+use crate::bitboard::*;
+use crate::moves::*;
+use crate::position::Position;
+use crate::things::*;
+use crate::variant::*;
+
+// Racing Kings: both sides start on the first two ranks and race their kings
+// to the eighth.  No move may give check, so there's no checkmate.  As white
+// moves first, black reaching the eighth rank straight after white's king
+// gets there is a draw.
+#[derive(Clone, Copy)]
+pub struct RacingKings;
+
+impl Variant for RacingKings {
+    fn filter_moves(pos: &Position, moves: &mut MoveList) {
+        moves.retain(|mv| !pos.gives_check(mv));
+    }
+
+    fn variant_outcome(pos: &Position) -> Option<Outcome> {
+        let goal = RANK_BITBOARDS[Rank8 as usize];
+        let white_home = pos.king(White) & goal != 0;
+        let black_home = pos.king(Black) & goal != 0;
+        match (white_home, black_home) {
+            (false, false) => None,
+            (true, true) => Some(Outcome::Draw),
+            (false, true) => Some(Outcome::Win(Black)),
+            (true, false) => {
+                // Black gets one more move if there's a safe goal square it
+                // can reach.  The king can't shield a square it's leaving.
+                if pos.side == Black {
+                    let black_king = pos.king(Black);
+                    let mut targets = king_attacks(black_king) & goal & !pos.color_pieces(Black);
+                    while targets != 0 {
+                        let sq = bb_pop(&mut targets);
+                        if pos.attackers_to(sq, pos.occupancy() & !black_king) & pos.color_pieces(White) == 0 {
+                            return None;
+                        }
+                    }
+                }
+                Some(Outcome::Win(White))
+            }
+        }
+    }
+
+    // With no check, running out of moves is always stalemate.
+    fn no_moves_outcome(_pos: &Position) -> Outcome {
+        Outcome::Draw
+    }
+}
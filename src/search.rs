@@ -0,0 +1,797 @@
+use crate::movepick::*;
+use crate::moves::*;
+use crate::pawns::*;
+use crate::position::Position;
+use crate::things::*;
+use crate::timeman::*;
+use crate::tt::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const INFINITY: i32 = 32000;
+pub const MATE: i32 = 31000;
+pub const MAX_PLY: usize = 128;
+
+// A capture that can't bring the score within this of alpha, even winning the
+// captured piece for nothing, isn't worth searching.
+const DELTA_MARGIN: i32 = 200;
+
+// Per ply of depth remaining.
+const FUTILITY_MARGIN: i32 = 100;
+const REVERSE_FUTILITY_MARGIN: i32 = 80;
+const RAZOR_MARGIN: i32 = 300;
+
+// The selective parts of the search, each of which can be switched off to see
+// what it's worth in nodes or in games.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchOptions {
+    pub null_move: bool,
+    pub lmr: bool, // late move reductions
+    pub futility: bool,
+    pub reverse_futility: bool,
+    pub razoring: bool,
+    pub check_extensions: bool,
+    pub iir: bool, // internal iterative reductions
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            null_move: true,
+            lmr: true,
+            futility: true,
+            reverse_futility: true,
+            razoring: true,
+            check_extensions: true,
+            iir: true,
+        }
+    }
+}
+
+impl SearchOptions {
+    // Plain alpha-beta.
+    pub fn none() -> SearchOptions {
+        SearchOptions {
+            null_move: false,
+            lmr: false,
+            futility: false,
+            reverse_futility: false,
+            razoring: false,
+            check_extensions: false,
+            iir: false,
+        }
+    }
+}
+
+// Mates are scored MATE less the number of plies to the mate, so that nearer
+// mates score higher.  Returns the number of moves to mate, negative if it's
+// the side to move being mated.
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score >= MATE - MAX_PLY as i32 {
+        Some((MATE - score + 1) / 2)
+    } else if score <= -MATE + MAX_PLY as i32 {
+        Some(-(MATE + score) / 2)
+    } else {
+        None
+    }
+}
+
+// When to stop searching, besides being told to.  The defaults search until
+// stopped or there's nothing left to find.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub depth: u32,
+    pub nodes: u64,
+    pub movetime: Option<Duration>,
+    pub clock: Option<Clock>,  // left to the time manager
+    pub move_overhead: Duration,
+    pub mate: Option<u32>,     // stop on finding a mate in this many moves
+    pub searchmoves: Vec<u16>, // only these root moves, if any are given
+    pub multipv: usize,        // how many of the best lines to find
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            depth: MAX_PLY as u32 - 1,
+            nodes: u64::MAX,
+            movetime: None,
+            clock: None,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            mate: None,
+            searchmoves: Vec::new(),
+            multipv: 1,
+        }
+    }
+}
+
+// Reported for each line after each iteration of the search, and returned at
+// the end for the best.
+#[derive(Clone, Debug, Default)]
+pub struct SearchInfo {
+    pub multipv: usize, // the line's rank, from 1
+    pub depth: u32,
+    pub score: i32, // from the side to move's point of view
+    pub nodes: u64,
+    pub nps: u64,
+    pub time: Duration,
+    pub hashfull: usize, // permille
+    pub pv: Vec<u16>,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> u16 {
+        self.pv.first().copied().unwrap_or(NULL_MOVE)
+    }
+}
+
+pub struct Search {
+    pos: Position,
+    history: Vec<u64>, // hashes of the positions before the current one
+    limits: Limits,
+    start: Instant,
+    clock_start: Instant, // the start, or when pondering stopped
+    time: Option<TimeManager>,
+    nodes: u64,
+    stopped: bool,
+    stop: Arc<AtomicBool>, // set by another thread to stop the search
+    ponder: Arc<AtomicBool>,
+    pondering: bool,
+    pawns: PawnTable,
+    tt: Arc<TranspositionTable>,
+    heuristics: Box<Heuristics>,
+    played: [u16; MAX_PLY], // the move made at each ply of the current line
+    lines: Vec<SearchInfo>, // the best lines, as of the last iteration
+    excluded: Vec<u16>,     // root moves already on a better line
+    root_hash_move: u16,
+    options: SearchOptions,
+    reductions: [[i32; 64]; 64], // by depth and move number
+
+    // Triangular PV table: pv[ply] holds the best line found from ply on,
+    // which is the move played there followed by pv[ply + 1].
+    pv: [[u16; MAX_PLY]; MAX_PLY],
+    pv_len: [usize; MAX_PLY],
+
+    // Lazy SMP: helpers search the same position on other threads, each a
+    // little differently, and what they find reaches this search through the
+    // transposition table they share.  Only this search's result counts.
+    helpers: Vec<Search>,
+    helper_stop: Arc<AtomicBool>, // stops all the helpers at once
+    helper_nodes: Vec<Arc<AtomicU64>>,
+    node_count: Arc<AtomicU64>, // nodes, published for the main search
+    depth_offset: i32,          // added to each iteration's depth
+}
+
+// Late moves are reduced by more the deeper the search and the later the move.
+fn reduction_table() -> [[i32; 64]; 64] {
+    let mut table = [[0; 64]; 64];
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (moves, reduction) in row.iter_mut().enumerate().skip(1) {
+            *reduction = (0.75 + (depth as f64).ln() * (moves as f64).ln() / 2.25) as i32;
+        }
+    }
+    table
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Search {
+    pub fn new() -> Search {
+        Search {
+            pos: Position::new(),
+            history: Vec::new(),
+            limits: Limits::default(),
+            start: Instant::now(),
+            clock_start: Instant::now(),
+            time: None,
+            nodes: 0,
+            stopped: false,
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: Arc::new(AtomicBool::new(false)),
+            pondering: false,
+            pawns: PawnTable::default(),
+            tt: Arc::new(TranspositionTable::default()),
+            heuristics: Box::default(),
+            played: [NULL_MOVE; MAX_PLY],
+            lines: Vec::new(),
+            excluded: Vec::new(),
+            root_hash_move: NULL_MOVE,
+            options: SearchOptions::default(),
+            reductions: reduction_table(),
+            pv: [[NULL_MOVE; MAX_PLY]; MAX_PLY],
+            pv_len: [0; MAX_PLY],
+            helpers: Vec::new(),
+            helper_stop: Arc::new(AtomicBool::new(false)),
+            helper_nodes: Vec::new(),
+            node_count: Arc::new(AtomicU64::new(0)),
+            depth_offset: 0,
+        }
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+        for helper in &mut self.helpers {
+            helper.options = options;
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.helpers.len() + 1
+    }
+
+    // Every other helper searches a ply deeper than this search, so that
+    // they don't all follow it move for move.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.helpers = (1..threads.max(1))
+            .map(|i| Search {
+                stop: self.helper_stop.clone(),
+                options: self.options,
+                depth_offset: (i % 2) as i32,
+                ..Search::with_tt(self.tt.clone())
+            })
+            .collect();
+        self.helper_nodes = self.helpers.iter().map(|helper| helper.node_count.clone()).collect();
+    }
+
+    // Setting the flag stops the search, which then returns the best move
+    // found so far.  It's left set afterwards, and must be cleared before the
+    // next search.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    // While the flag is set the search ignores its time limits, as it's
+    // thinking on the opponent's time.  Clearing it starts the clock.
+    pub fn ponder_flag(&self) -> Arc<AtomicBool> {
+        self.ponder.clone()
+    }
+
+    // Forget everything learned from earlier searches, as for a new game.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        *self.heuristics = Heuristics::default();
+        self.lines.clear();
+        for helper in &mut self.helpers {
+            *helper.heuristics = Heuristics::default();
+        }
+    }
+
+    // The lines found by the last search, best first: as many as it was asked
+    // for, unless there weren't enough moves.
+    pub fn lines(&self) -> &[SearchInfo] {
+        &self.lines
+    }
+
+    // Searches can share a transposition table.
+    pub fn with_tt(tt: Arc<TranspositionTable>) -> Search {
+        Search { tt, ..Search::new() }
+    }
+
+    pub fn tt(&self) -> &Arc<TranspositionTable> {
+        &self.tt
+    }
+
+    // Resizing clears the table.  A table shared with other searches is left
+    // to them and this search gets a new one, as do its helpers.
+    pub fn resize_tt(&mut self, mb: usize) {
+        let threads = self.threads();
+        self.set_threads(1);
+        match Arc::get_mut(&mut self.tt) {
+            Some(tt) => tt.resize(mb),
+            None => self.tt = Arc::new(TranspositionTable::new(mb)),
+        }
+        self.set_threads(threads);
+    }
+
+    // Search pos, deepening one ply at a time until a limit is reached.  The
+    // history holds the hashes of the positions played before pos, oldest
+    // first, to spot repetitions.  info is called for each line, best first,
+    // as each depth completes.  Any helpers search alongside until it's done.
+    pub fn search<F: FnMut(&SearchInfo)>(
+        &mut self,
+        pos: &Position,
+        history: &[u64],
+        limits: Limits,
+        info: F,
+    ) -> SearchInfo {
+        self.tt.new_search();
+        self.helper_stop.store(false, Ordering::Relaxed);
+        for nodes in &self.helper_nodes {
+            nodes.store(0, Ordering::Relaxed);
+        }
+
+        // Helpers search until they're stopped, limited only in the moves
+        // they may play.
+        let helper_limits = Limits {
+            searchmoves: limits.searchmoves.clone(),
+            ..Limits::default()
+        };
+        let mut helpers = std::mem::take(&mut self.helpers);
+        let mut result = thread::scope(|scope| {
+            for helper in helpers.iter_mut() {
+                let limits = helper_limits.clone();
+                scope.spawn(move || helper.iterate(pos, history, limits, |_| {}));
+            }
+            let result = self.iterate(pos, history, limits, info);
+            self.helper_stop.store(true, Ordering::Relaxed);
+            result
+        });
+        self.helpers = helpers;
+
+        // The helpers have all finished, so their counts are final.
+        result.nodes = self.total_nodes();
+        result.nps = (result.nodes as u128 * 1_000_000 / self.start.elapsed().as_micros().max(1)) as u64;
+        result
+    }
+
+    fn total_nodes(&self) -> u64 {
+        self.nodes + self.helper_nodes.iter().map(|nodes| nodes.load(Ordering::Relaxed)).sum::<u64>()
+    }
+
+    fn iterate<F: FnMut(&SearchInfo)>(
+        &mut self,
+        pos: &Position,
+        history: &[u64],
+        limits: Limits,
+        mut info: F,
+    ) -> SearchInfo {
+        self.start = Instant::now();
+        self.clock_start = self.start;
+        self.pondering = self.ponder.load(Ordering::Relaxed);
+        self.time = limits.clock.map(|clock| TimeManager::new(&clock, limits.move_overhead));
+        self.pos = *pos;
+        self.history = history.to_vec();
+        self.limits = limits;
+        self.nodes = 0;
+        self.stopped = false;
+        self.heuristics.killers = [[NULL_MOVE; 2]; MAX_PLY];
+
+        let mut move_generator = MoveGen::new(pos);
+        move_generator.gen_legal_moves();
+        let root_moves = move_generator.moves;
+        self.excluded.clear();
+        let allowed: Vec<u16> = root_moves.iter().copied().filter(|&mv| self.is_root_move(mv)).collect();
+        let multipv = self.limits.multipv.clamp(1, allowed.len().max(1));
+
+        let mut result = SearchInfo::default();
+        for depth in 1..=self.limits.depth.clamp(1, MAX_PLY as u32 - 1) {
+            // Each line is the best move left once the ones before it are
+            // taken out, trying first the best of those left last iteration.
+            let mut lines = Vec::new();
+            self.excluded.clear();
+            for k in 0..multipv {
+                let mut previous = self.lines.iter().map(|line| line.best_move());
+                self.root_hash_move = previous.find(|mv| !self.excluded.contains(mv)).unwrap_or(NULL_MOVE);
+                let score = self.negamax(depth as i32 + self.depth_offset, 0, -INFINITY, INFINITY);
+
+                // An unfinished iteration is no use, unless it's all there is.
+                if self.stopped && (depth > 1 || k > 0) {
+                    break;
+                }
+
+                let time = self.start.elapsed();
+                let nodes = self.total_nodes();
+                lines.push(SearchInfo {
+                    multipv: k + 1,
+                    depth,
+                    score,
+                    nodes,
+                    nps: (nodes as u128 * 1_000_000 / time.as_micros().max(1)) as u64,
+                    time,
+                    hashfull: self.tt.hashfull(),
+                    pv: self.pv[0][..self.pv_len[0]].to_vec(),
+                });
+                if self.stopped || self.pv_len[0] == 0 {
+                    break;
+                }
+                self.excluded.push(self.pv[0][0]);
+            }
+            if self.stopped && depth > 1 {
+                break;
+            }
+
+            // A line can come out better than one found before it, when that
+            // was searched against a different move.
+            lines.sort_by_key(|line| -line.score);
+            for (k, line) in lines.iter_mut().enumerate() {
+                line.multipv = k + 1;
+                info(line);
+            }
+            self.lines = lines;
+            result = self.lines[0].clone();
+            let score = result.score;
+
+            // Nothing more to learn once there's no move, or a mate has been
+            // found well within the depth searched on every line.  Pruning can
+            // hide a quicker one nearer the horizon.
+            let mated = |line: &SearchInfo| 2 * (MATE - line.score.abs()) <= depth as i32;
+            if self.stopped || result.pv.is_empty() || self.lines.iter().all(mated) {
+                break;
+            }
+            if let (Some(moves), Some(found)) = (self.limits.mate, mate_in(score)) {
+                if found > 0 && found <= moves as i32 {
+                    break;
+                }
+            }
+
+            // With time to spend, on the clock or per move, a forced move
+            // isn't worth any of it.  Otherwise the time manager decides if
+            // another iteration is.  Searches to a depth, or until stopped,
+            // go on regardless.
+            self.update_pondering();
+            if !self.pondering {
+                let timed = self.time.is_some() || self.limits.movetime.is_some();
+                if timed && root_moves.len() == 1 {
+                    break;
+                }
+                if let Some(time) = &mut self.time {
+                    if !time.keep_going(self.clock_start.elapsed(), result.best_move(), score) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Stopped before a single move was searched, any move beats none.
+        if result.pv.is_empty() {
+            result.pv.extend(allowed.first());
+        }
+        self.node_count.store(self.nodes, Ordering::Relaxed);
+        result
+    }
+
+    fn check_limits(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes >= self.limits.nodes {
+            self.stopped = true;
+        }
+        // Often enough to stop promptly, but not so often as to slow things.
+        if self.nodes.is_multiple_of(1024) {
+            self.node_count.store(self.nodes, Ordering::Relaxed);
+            if self.total_nodes() >= self.limits.nodes {
+                self.stopped = true;
+            }
+            self.update_pondering();
+            let hard_limit = self.time.as_ref().map(|time| time.hard_limit());
+            let limit = match (self.limits.movetime, hard_limit) {
+                (Some(movetime), Some(hard_limit)) => Some(movetime.min(hard_limit)),
+                (movetime, hard_limit) => movetime.or(hard_limit),
+            };
+            let out_of_time = !self.pondering && limit.is_some_and(|limit| self.clock_start.elapsed() >= limit);
+            if out_of_time || self.stop.load(Ordering::Relaxed) {
+                self.stopped = true;
+            }
+        }
+        self.stopped
+    }
+
+    // The clock starts when pondering stops.
+    fn update_pondering(&mut self) {
+        if self.pondering && !self.ponder.load(Ordering::Relaxed) {
+            self.pondering = false;
+            self.clock_start = Instant::now();
+        }
+    }
+
+    fn negamax(&mut self, mut depth: i32, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        let original_alpha = alpha;
+        let pv_node = beta - alpha > 1;
+        self.pv_len[ply] = 0;
+        if self.check_limits() {
+            return 0;
+        }
+
+        if ply > 0 && (self.is_repetition() || self.is_fifty_move_draw()) {
+            return 0;
+        }
+
+        // Look a ply further when in check, so that a line doesn't end in it.
+        let in_check = self.pos.calc_checkers() != 0;
+        if in_check && self.options.check_extensions {
+            depth += 1;
+        }
+
+        if depth <= 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(ply, alpha, beta);
+        }
+
+        // A deep enough result from before may settle this node outright.  The
+        // root always searches, so that it has a best move to return.
+        let hash = self.pos.hash();
+        let entry = self.tt.probe(hash, ply);
+        if let Some(entry) = entry {
+            if ply > 0 && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower if entry.score >= beta => return entry.score,
+                    Bound::Upper if entry.score <= alpha => return entry.score,
+                    _ => {}
+                }
+            }
+        }
+
+        // Try the best move from before first.  At the root that's the last
+        // iteration's, which the table may have lost.
+        let hash_move = if ply == 0 {
+            self.root_hash_move
+        } else {
+            entry.map_or(NULL_MOVE, |entry| entry.mv)
+        };
+        let prev = if ply > 0 { self.played[ply - 1] } else { NULL_MOVE };
+        let eval = if in_check { -INFINITY } else { self.evaluate() };
+
+        // Away from the principal variation, a position that's already far
+        // enough outside the window is unlikely to come back into it.
+        if !pv_node && !in_check {
+            if self.options.reverse_futility
+                && depth <= 6
+                && eval - REVERSE_FUTILITY_MARGIN * depth >= beta
+                && mate_in(beta).is_none()
+            {
+                return eval;
+            }
+
+            if self.options.razoring && depth <= 2 && eval + RAZOR_MARGIN * depth < alpha {
+                let score = self.quiescence(ply, alpha - 1, alpha);
+                if score < alpha {
+                    return score;
+                }
+            }
+
+            // If passing still fails high, a real move almost certainly would.
+            // That's not so in zugzwang, which is most likely with only pawns.
+            if self.options.null_move
+                && depth >= 3
+                && eval >= beta
+                && prev != NULL_MOVE
+                && self.has_non_pawn_material()
+            {
+                let reduction = 3 + depth / 6;
+                self.history.push(hash);
+                self.played[ply] = NULL_MOVE;
+                let undo = self.pos.make_null_move();
+                let score = -self.negamax(depth - 1 - reduction, ply + 1, -beta, -beta + 1);
+                self.pos.unmake_null_move(undo);
+                self.history.pop();
+
+                if self.stopped {
+                    return 0;
+                }
+                if score >= beta {
+                    // The mate was found without a real move; don't trust it.
+                    return if mate_in(score).is_some() { beta } else { score };
+                }
+            }
+        }
+
+        // Without a hash move to try first this node is probably new, and not
+        // worth as much effort.
+        if self.options.iir && ply > 0 && depth >= 4 && hash_move == NULL_MOVE {
+            depth -= 1;
+        }
+
+        let counter = self.heuristics.counter(prev);
+        let mut picker = MovePicker::new(&self.pos, hash_move, self.heuristics.killers[ply], counter);
+
+        let mut best = -INFINITY;
+        let mut best_move = NULL_MOVE;
+        let mut legal_moves = 0;
+        let mut quiets_tried = MoveList::new();
+        while let Some(mv) = picker.next(&self.heuristics) {
+            if ply == 0 && !self.is_root_move(mv) {
+                continue;
+            }
+            legal_moves += 1;
+            let quiet = is_quiet(&self.pos, mv);
+            let gives_check = self.pos.gives_check(mv);
+            let ordinary = quiet && !in_check && !gives_check;
+
+            // Near the leaves, a quiet move can't be expected to make up much
+            // of a deficit.
+            if self.options.futility
+                && ordinary
+                && depth <= 3
+                && best > -MATE + MAX_PLY as i32
+                && eval + FUTILITY_MARGIN * depth <= alpha
+            {
+                continue;
+            }
+
+            self.history.push(hash);
+            self.played[ply] = mv;
+            let undo = self.pos.make_move(mv);
+
+            // The first move is searched with the full window.  The rest only
+            // need to be shown worse than it, which a null window does more
+            // cheaply; and the later they come, the less deeply, unless they
+            // turn out better after all.
+            let score = if legal_moves == 1 {
+                -self.negamax(depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                let mut reduction = 0;
+                if self.options.lmr && ordinary && depth >= 3 {
+                    reduction = self.reductions[depth.min(63) as usize][legal_moves.min(63)];
+                    if pv_node {
+                        reduction -= 1;
+                    }
+                    reduction = reduction.clamp(0, depth - 2);
+                }
+                let mut score = -self.negamax(depth - 1 - reduction, ply + 1, -alpha - 1, -alpha);
+                if score > alpha && reduction > 0 {
+                    score = -self.negamax(depth - 1, ply + 1, -alpha - 1, -alpha);
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(depth - 1, ply + 1, -beta, -alpha);
+                }
+                score
+            };
+
+            self.pos.unmake_move(undo);
+            self.history.pop();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = mv;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply, mv);
+                    if alpha >= beta {
+                        if quiet {
+                            let us = self.pos.us();
+                            self.heuristics.update_quiet(us, ply, prev, mv, depth, &quiets_tried);
+                        }
+                        break;
+                    }
+                }
+            }
+            if quiet {
+                quiets_tried.push(mv);
+            }
+        }
+
+        if legal_moves == 0 {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        // With moves left out, the root's score isn't the position's.
+        if ply == 0 && !self.excluded.is_empty() {
+            return best;
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(hash, ply, best_move, best, depth, bound);
+        best
+    }
+
+    fn is_root_move(&self, mv: u16) -> bool {
+        (self.limits.searchmoves.is_empty() || self.limits.searchmoves.contains(&mv)) && !self.excluded.contains(&mv)
+    }
+
+    fn has_non_pawn_material(&self) -> bool {
+        let us = self.pos.us();
+        [Knight, Bishop, Rook, Queen].iter().any(|&piece| self.pos.pieces(piece, us) != 0)
+    }
+
+    // Search captures until the position is quiet, so that the evaluation isn't
+    // taken in the middle of an exchange.  The side to move can stand pat on
+    // the static evaluation instead of capturing, unless it's in check.
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv_len[ply] = 0;
+        if self.check_limits() {
+            return 0;
+        }
+
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate();
+        }
+
+        let in_check = self.pos.calc_checkers() != 0;
+        let mut best = -INFINITY;
+        let mut stand_pat = 0;
+        if !in_check {
+            stand_pat = self.evaluate();
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best = stand_pat;
+        }
+
+        let mut move_generator = MoveGen::new(&self.pos);
+        move_generator.gen_captures();
+        let mut moves = move_generator.moves;
+        if in_check && moves.is_empty() {
+            return -MATE + ply as i32;
+        }
+
+        let pos = self.pos;
+        moves.sort_by_key(|&mv| -mvv_lva(&pos, mv));
+
+        for mv in moves {
+            if !in_check {
+                if mv_get_kind(mv) != MOVE_PROMO && stand_pat + captured_value(&self.pos, mv) + DELTA_MARGIN <= alpha {
+                    continue;
+                }
+                if !self.pos.see_ge(mv, 0) {
+                    continue;
+                }
+            }
+
+            let undo = self.pos.make_move(mv);
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.pos.unmake_move(undo);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply, mv);
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn update_pv(&mut self, ply: usize, mv: u16) {
+        let len = self.pv_len[ply + 1];
+        let (this, rest) = self.pv.split_at_mut(ply + 1);
+        this[ply][0] = mv;
+        this[ply][1..=len].copy_from_slice(&rest[0][..len]);
+        self.pv_len[ply] = len + 1;
+    }
+
+    // Has the current position been seen before?  Only positions since the
+    // last capture or pawn move, with the same side to move, can match.
+    fn is_repetition(&self) -> bool {
+        let hash = self.pos.hash();
+        let reversible = (self.pos.halfmove_clock() as usize).min(self.history.len());
+        (2..=reversible)
+            .step_by(2)
+            .any(|distance| self.history[self.history.len() - distance] == hash)
+    }
+
+    // Checkmate takes precedence over the fifty-move rule.
+    fn is_fifty_move_draw(&self) -> bool {
+        if self.pos.halfmove_clock() < 100 {
+            return false;
+        }
+        if self.pos.calc_checkers() == 0 {
+            return true;
+        }
+        let mut move_generator = MoveGen::new(&self.pos);
+        move_generator.gen_legal_moves();
+        !move_generator.moves.is_empty()
+    }
+
+    fn evaluate(&mut self) -> i32 {
+        let pawns = self.pawns.probe(&self.pos);
+        self.pos.evaluate_with_pawns(&pawns)
+    }
+}
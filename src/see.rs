@@ -0,0 +1,138 @@
+use crate::attack::*;
+use crate::bitboard::*;
+use crate::moves::*;
+use crate::position::Position;
+use crate::square::*;
+use crate::things::*;
+
+// Piece values used by the static exchange evaluator, indexed by piece.  A
+// king is never actually captured during an exchange, so its value only has
+// to be large enough to never be worth giving up.
+pub const SEE_VALUES: [i32; NUM_PIECES] = [100, 300, 300, 500, 900, 20000];
+
+const SEE_PIECES: [Piece; NUM_PIECES] = [Pawn, Knight, Bishop, Rook, Queen, King];
+
+impl Position {
+    // Static exchange evaluation: the material balance, from the moving side's
+    // point of view, once every capture and recapture on the move's
+    // destination square has been played out with the least valuable
+    // attacker first.  Either side may stop capturing when it's not
+    // profitable to continue.  Pins are not taken into account.
+    pub fn see(&self, mv: u16) -> i32 {
+        self.see_with_values(mv, &SEE_VALUES)
+    }
+
+    // Does the move win at least threshold according to SEE?
+    pub fn see_ge(&self, mv: u16, threshold: i32) -> bool {
+        self.see_ge_with_values(mv, threshold, &SEE_VALUES)
+    }
+
+    pub fn see_ge_with_values(&self, mv: u16, threshold: i32, values: &[i32; NUM_PIECES]) -> bool {
+        self.see_with_values(mv, values) >= threshold
+    }
+
+    pub fn see_with_values(&self, mv: u16, values: &[i32; NUM_PIECES]) -> i32 {
+        let src = mv_get_src(mv);
+        let dst = mv_get_dst(mv);
+        let kind = mv_get_kind(mv);
+
+        if kind == MOVE_CASTLE {
+            return 0;
+        }
+
+        let dst_bb = bb_from_sq(dst);
+        let promo_ranks = RANK_BITBOARDS[Rank1 as usize] | RANK_BITBOARDS[Rank8 as usize];
+        let mut occupancy = self.occupancy() & !bb_from_sq(src);
+        let mut side = self.color_on(src);
+        let mut victim = self.piece_on(src); // piece that's on dst after each capture
+        let mut gain = [0i32; 32];
+        let mut depth = 0;
+
+        match kind {
+            MOVE_DROP => {
+                // Nothing is captured, but the dropped piece may be.
+                occupancy |= dst_bb;
+                side = self.us();
+                victim = mv_get_drop_piece(mv);
+            }
+            MOVE_ENPASSANT => {
+                // The captured pawn isn't on dst; take it off the board so that
+                // any x-ray attackers behind it are discovered.
+                let captured_sq = make_sq(rank_of(src), file_of(dst));
+                occupancy &= !bb_from_sq(captured_sq);
+                gain[0] = values[Pawn as usize];
+            }
+            MOVE_PROMO => {
+                let promoted = mv_get_promo_piece(mv);
+                if self.piece_on(dst) != NoPiece {
+                    gain[0] = values[self.piece_on(dst) as usize];
+                }
+                gain[0] += values[promoted as usize] - values[Pawn as usize];
+                victim = promoted;
+            }
+            _ => {
+                if self.piece_on(dst) != NoPiece {
+                    gain[0] = values[self.piece_on(dst) as usize];
+                }
+            }
+        }
+
+        let diagonals = self.pieces(Bishop, White)
+            | self.pieces(Bishop, Black)
+            | self.pieces(Queen, White)
+            | self.pieces(Queen, Black);
+        let straights = self.pieces(Rook, White)
+            | self.pieces(Rook, Black)
+            | self.pieces(Queen, White)
+            | self.pieces(Queen, Black);
+        let mut attackers = self.attackers_to(dst, occupancy);
+
+        loop {
+            side = opposite(side);
+            let ours = attackers & self.color_pieces(side);
+            if ours == 0 {
+                break;
+            }
+
+            // Find our least valuable attacker.
+            let mut attacker = NoPiece;
+            let mut attacker_bb: u64 = 0;
+            for piece in SEE_PIECES {
+                let bb = ours & self.pieces(piece, side);
+                if bb != 0 {
+                    attacker = piece;
+                    attacker_bb = bb_from_sq(bb_lsb(bb));
+                    break;
+                }
+            }
+
+            // The king may only recapture if the square is no longer defended.
+            if attacker == King && attackers & self.color_pieces(opposite(side)) != 0 {
+                break;
+            }
+
+            // Speculatively capture, then see what the other side can do.
+            depth += 1;
+            gain[depth] = values[victim as usize] - gain[depth - 1];
+            victim = attacker;
+            if attacker == Pawn && dst_bb & promo_ranks != 0 {
+                gain[depth] += values[Queen as usize] - values[Pawn as usize];
+                victim = Queen;
+            }
+
+            // Lifting the attacker off the board may uncover a slider behind it.
+            occupancy &= !attacker_bb;
+            attackers |= bishop_attacks(dst_bb, occupancy) & diagonals;
+            attackers |= rook_attacks(dst_bb, occupancy) & straights;
+            attackers &= occupancy;
+        }
+
+        // Walk back through the exchange; at each step the side to move can
+        // choose not to capture.
+        while depth > 0 {
+            gain[depth - 1] = -std::cmp::max(-gain[depth - 1], gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+}
@@ -32,7 +32,7 @@ pub fn sq_to_str(sq: u8) -> String {
     }
     let rank = rank_of(sq);
     let file = file_of(sq);
-    s.push((file + 'a' as u8) as char);
+    s.push((file + b'a') as char);
     s.push_str(&(rank+1).to_string());
     s
 }
\ No newline at end of file
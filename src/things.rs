@@ -1,15 +1,15 @@
 #[repr(u8)]
-#[derive(Clone,Copy,PartialEq)]
+#[derive(Clone,Copy,Debug,PartialEq)]
 pub enum Rank { Rank1, Rank2, Rank3, Rank4, Rank5, Rank6, Rank7, Rank8 }
 pub const NUM_RANKS: usize = 8;
 
 #[repr(u8)]
-#[derive(Clone,Copy,PartialEq)]
+#[derive(Clone,Copy,Debug,PartialEq)]
 pub enum File { FileA, FileB, FileC, FileD, FileE, FileF, FileG, FileH }
 pub const NUM_FILES: usize = 8;
 
 #[repr(u8)]
-#[derive(Clone,Copy,PartialEq)]
+#[derive(Clone,Copy,Debug,PartialEq)]
 pub enum Piece {
     Pawn = 0,
     Knight = 1,
@@ -22,7 +22,7 @@ pub enum Piece {
 pub const NUM_PIECES: usize = 6;
 
 #[repr(u8)]
-#[derive(Clone,Copy,PartialEq)]
+#[derive(Clone,Copy,Debug,PartialEq)]
 pub enum Color {
     White,
     Black,
@@ -63,6 +63,14 @@ pub fn piece_to_str(piece: Piece, color: Color) -> String {
     }
 }
 
+pub fn opposite(color: Color) -> Color {
+    match color {
+        White => Black,
+        Black => White,
+        _ => panic!("bogus color"),
+    }
+}
+
 pub fn color_from_char(c: char) -> Color {
     match c.is_ascii_uppercase() {
         true => White,
@@ -73,4 +81,5 @@ pub fn color_from_char(c: char) -> Color {
 pub const WHITE_OO: u8 = 1;
 pub const WHITE_OOO: u8 = 2;
 pub const BLACK_OO: u8 = 4;
-pub const BLACK_OOO: u8 = 8;
\ No newline at end of file
+pub const BLACK_OOO: u8 = 8;
+pub const NUM_CASTLE_RIGHTS: usize = 4;
\ No newline at end of file
@@ -0,0 +1,75 @@
+use crate::moves::*;
+use crate::position::{IllegalFen, Position};
+use crate::things::*;
+use crate::variant::*;
+
+const CHECKS_TO_WIN: u8 = 3;
+
+// White's and black's counts from check counters, if there are exactly two
+// and neither is more than it takes to win.
+fn parse_checks(checks: &str) -> Option<[u8; NUM_COLORS]> {
+    let counts: Vec<&str> = checks.trim_start_matches('+').split('+').collect();
+    let [white, black] = counts[..] else {
+        return None;
+    };
+    let count = |count: &str| count.parse::<u8>().ok().filter(|&count| count <= CHECKS_TO_WIN);
+    Some([count(white)?, count(black)?])
+}
+
+// Three-check: standard chess, except that giving check for the third time
+// also wins.  The checks each side has given are kept in the position.
+#[derive(Clone, Copy)]
+pub struct ThreeCheck;
+
+impl Variant for ThreeCheck {
+    // The side now to move is in check from the move just made.
+    fn after_move(pos: &mut Position, _undo: &mut Undo) {
+        if pos.calc_checkers() != 0 {
+            pos.checks[pos.enemy() as usize] += 1;
+        }
+    }
+
+    fn before_unmake(pos: &mut Position, _undo: &Undo) {
+        if pos.calc_checkers() != 0 {
+            pos.checks[pos.enemy() as usize] -= 1;
+        }
+    }
+
+    fn variant_outcome(pos: &Position) -> Option<Outcome> {
+        for color in [White, Black] {
+            if pos.checks[color as usize] >= CHECKS_TO_WIN {
+                return Some(Outcome::Win(color));
+            }
+        }
+        None
+    }
+
+    // Checks are either counted up as checks given ("+2+1", after the move
+    // counters) or down as checks remaining ("1+2", before them).
+    // Counters that don't parse are ignored; check_fen_extensions() is
+    // there to turn them away first.
+    fn parse_fen_extensions(pos: &mut Position, ext: &FenExtensions) {
+        let Some(checks) = ext.checks else {
+            return;
+        };
+        let Some(counts) = parse_checks(checks) else {
+            return;
+        };
+        let given = checks.starts_with('+');
+        for color in [White, Black] {
+            let count = counts[color as usize];
+            pos.checks[color as usize] = if given { count } else { CHECKS_TO_WIN.saturating_sub(count) };
+        }
+    }
+
+    fn check_fen_extensions(ext: &FenExtensions) -> Result<(), IllegalFen> {
+        match ext.checks {
+            Some(checks) if parse_checks(checks).is_none() => Err(IllegalFen("bad check counters")),
+            _ => Ok(()),
+        }
+    }
+
+    fn fen_suffix(pos: &Position) -> String {
+        format!(" +{}+{}", pos.checks[White as usize], pos.checks[Black as usize])
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+// Without a move count, assume the game lasts this many more moves.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+// Time lost to the protocol and the GUI on each move.
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+pub const MAX_MOVE_OVERHEAD: u64 = 5000; // milliseconds
+
+// The clock for the side to move.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    pub time: Duration,
+    pub increment: Duration,
+    pub movestogo: Option<u32>, // moves until the next time control
+}
+
+// Decides when iterative deepening has spent long enough on a move.  The soft
+// limit is checked between iterations, and stretched or shrunk depending on
+// how the search is going; the hard limit stops the search wherever it is.
+pub struct TimeManager {
+    soft: Duration,
+    hard: Duration,
+    best_move: u16,
+    stable_iterations: u32, // with the same best move
+    previous_score: Option<i32>,
+}
+
+impl TimeManager {
+    pub fn new(clock: &Clock, move_overhead: Duration) -> TimeManager {
+        let available = clock.time.saturating_sub(move_overhead);
+        let moves = clock.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        // Never risk most of what's left on one move.
+        let max_time = available * 4 / 5;
+        let soft = (available / moves + clock.increment * 3 / 4).min(max_time);
+        TimeManager {
+            soft,
+            hard: (soft * 3).min(max_time),
+            best_move: 0,
+            stable_iterations: 0,
+            previous_score: None,
+        }
+    }
+
+    pub fn soft_limit(&self) -> Duration {
+        self.soft
+    }
+
+    pub fn hard_limit(&self) -> Duration {
+        self.hard
+    }
+
+    // Called as each iteration completes: is there time for another?  A best
+    // move that keeps coming back needs less checking, and a falling score
+    // more.
+    pub fn keep_going(&mut self, elapsed: Duration, best_move: u16, score: i32) -> bool {
+        if best_move == self.best_move {
+            self.stable_iterations += 1;
+        } else {
+            self.best_move = best_move;
+            self.stable_iterations = 0;
+        }
+        let mut scale = 1.3 - 0.1 * self.stable_iterations.min(6) as f64;
+
+        if let Some(previous) = self.previous_score {
+            let drop = (previous - score).clamp(0, 200);
+            if drop > 20 {
+                scale *= 1.0 + drop as f64 / 200.0;
+            }
+        }
+        self.previous_score = Some(score);
+
+        elapsed < self.soft.mul_f64(scale).min(self.hard)
+    }
+}
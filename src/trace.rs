@@ -0,0 +1,145 @@
+use crate::bitboard::*;
+use crate::eval::*;
+use crate::pawns::*;
+use crate::position::Position;
+use crate::things::*;
+use std::fmt;
+
+// The static evaluation broken down into its terms, to see why a position is
+// judged the way it is.  Each term is scored for each color from that color's
+// point of view, and the evaluation is white's terms less black's.
+pub struct EvalTrace {
+    pub terms: Vec<(&'static str, [Score; NUM_COLORS])>,
+    pub phase: i32,
+    pub score: i32, // the blended total, from white's point of view
+}
+
+impl EvalTrace {
+    pub fn new(pos: &Position) -> EvalTrace {
+        // The position keeps material and piece-square values together, so
+        // split them apart again here.
+        let mut material = [Score::default(); NUM_COLORS];
+        let mut pst = [Score::default(); NUM_COLORS];
+        for color in [White, Black] {
+            let c = color as usize;
+            for piece in [Pawn, Knight, Bishop, Rook, Queen, King] {
+                let value = Score::new(MG_VALUES[piece as usize], EG_VALUES[piece as usize]);
+                let mut pieces = pos.pieces(piece, color);
+                while pieces != 0 {
+                    let psq = psq_score(piece, color, bb_pop(&mut pieces));
+                    let psq = if color == White { psq } else { -psq };
+                    material[c] += value;
+                    pst[c] += psq - value;
+                }
+            }
+        }
+
+        let pawns = PawnStructure::new(pos);
+        let terms = vec![
+            ("Material", material),
+            ("PST", pst),
+            ("Pawns", [pawn_structure(&pawns, White), pawn_structure(&pawns, Black)]),
+            ("King safety", [king_safety(pos, White), king_safety(pos, Black)]),
+            ("Mobility", [mobility(pos, White), mobility(pos, Black)]),
+        ];
+
+        let mut trace = EvalTrace {
+            terms,
+            phase: pos.phase(),
+            score: 0,
+        };
+        trace.score = taper(trace.total(), trace.phase);
+        trace
+    }
+
+    // Sum of every term, white's less black's.
+    pub fn total(&self) -> Score {
+        let mut total = Score::default();
+        for (_, scores) in &self.terms {
+            total += scores[White as usize] - scores[Black as usize];
+        }
+        total
+    }
+
+    // For tuning scripts, e.g.
+    // {"phase":24,"score":35,"terms":{"material":{"white":{"mg":..,"eg":..},..}}}
+    pub fn to_json(&self) -> String {
+        fn score_json(score: Score) -> String {
+            format!("{{\"mg\":{},\"eg\":{}}}", score.mg, score.eg)
+        }
+
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|(name, scores)| {
+                let white = scores[White as usize];
+                let black = scores[Black as usize];
+                format!(
+                    "\"{}\":{{\"white\":{},\"black\":{},\"total\":{}}}",
+                    name.to_lowercase().replace(' ', "_"),
+                    score_json(white),
+                    score_json(black),
+                    score_json(white - black)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"phase\":{},\"score\":{},\"total\":{},\"terms\":{{{}}}}}",
+            self.phase,
+            self.score,
+            score_json(self.total()),
+            terms.join(",")
+        )
+    }
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rule = "-------------+-------------+-------------+------------";
+        writeln!(f, "        Term |    White    |    Black    |    Total")?;
+        writeln!(f, "             |   MG    EG  |   MG    EG  |   MG    EG")?;
+        writeln!(f, "{}", rule)?;
+        for (name, scores) in &self.terms {
+            let white = scores[White as usize];
+            let black = scores[Black as usize];
+            let total = white - black;
+            writeln!(
+                f,
+                "{:>12} | {:>5} {:>5} | {:>5} {:>5} | {:>5} {:>5}",
+                name, white.mg, white.eg, black.mg, black.eg, total.mg, total.eg
+            )?;
+        }
+        writeln!(f, "{}", rule)?;
+        let total = self.total();
+        writeln!(f, "{:>12} |             |             | {:>5} {:>5}", "Total", total.mg, total.eg)?;
+        writeln!(f)?;
+        writeln!(f, "Phase {}/{}", self.phase.min(MAX_PHASE), MAX_PHASE)?;
+        write!(f, "Score {} (white's point of view)", self.score)
+    }
+}
+
+impl Position {
+    pub fn trace(&self) -> EvalTrace {
+        EvalTrace::new(self)
+    }
+
+    // Like debug(), with the evaluation trace to the right of the board.
+    pub fn debug_eval(&self) {
+        let trace = self.trace().to_string();
+        let board = self.board_to_str();
+        let board: Vec<&str> = board.lines().collect();
+        let table: Vec<&str> = trace.lines().collect();
+        // The FEN on the last line can be wider than the board itself.
+        let width = board[..board.len() - 1].iter().map(|line| line.len()).max().unwrap_or(0);
+
+        // Line the table up with the top of the board, below the file letters.
+        let mut s = String::new();
+        for i in 0..board.len().max(table.len() + 2) {
+            let left = board.get(i).copied().unwrap_or("");
+            let right = if i >= 2 { table.get(i - 2).copied().unwrap_or("") } else { "" };
+            s.push_str(format!("{:<width$}   {}", left, right).trim_end());
+            s.push('\n');
+        }
+        print!("{}", s);
+    }
+}
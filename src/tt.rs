@@ -0,0 +1,216 @@
+use crate::moves::*;
+use crate::search::*;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    Exact = 1, // the score is exact
+    Lower = 2, // the search failed high, so the score is at least this
+    Upper = 3, // the search failed low, so the score is at most this
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TtEntry {
+    pub mv: u16,
+    pub score: i32,
+    pub depth: i32,
+    pub bound: Bound,
+}
+
+// Each entry is two words: the data, and the key XORed with the data.  Threads
+// read and write the words without locking, so an entry can be torn by two
+// threads storing at once; the XOR means a torn entry fails to match its key
+// rather than returning someone else's data.
+//
+// The data is packed as move (16 bits), score (16), depth (8), bound (2) and
+// age (6).  A bound of zero marks an empty entry.  Depths beyond what fits are
+// stored as the deepest that does, as helpers and extensions can go past it.
+#[derive(Default)]
+struct Entry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+const BUCKET_SIZE: usize = 4;
+const AGE_CYCLE: u8 = 64;
+
+// A bucket fills a cache line, so probing one costs a single memory access.
+#[repr(align(64))]
+#[derive(Default)]
+struct Bucket {
+    entries: [Entry; BUCKET_SIZE],
+}
+
+fn pack(mv: u16, score: i32, depth: i32, bound: Bound, age: u8) -> u64 {
+    mv as u64
+        | (score as i16 as u16 as u64) << 16
+        | (depth.clamp(i8::MIN as i32, i8::MAX as i32) as i8 as u8 as u64) << 32
+        | (bound as u64) << 40
+        | (age as u64) << 42
+}
+
+fn unpack_bound(data: u64) -> Option<Bound> {
+    match (data >> 40) & 0x3 {
+        1 => Some(Bound::Exact),
+        2 => Some(Bound::Lower),
+        3 => Some(Bound::Upper),
+        _ => None,
+    }
+}
+
+fn unpack_depth(data: u64) -> i32 {
+    (data >> 32) as u8 as i8 as i32
+}
+
+fn unpack_age(data: u64) -> u8 {
+    (data >> 42) as u8 & (AGE_CYCLE - 1)
+}
+
+// Mate scores are stored relative to the node rather than the root, so that
+// they stay right when the same position is reached at a different ply.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: AtomicU8, // bumped for each new search
+}
+
+impl TranspositionTable {
+    pub fn new(mb: usize) -> TranspositionTable {
+        let mut tt = TranspositionTable {
+            buckets: Vec::new(),
+            age: AtomicU8::new(0),
+        };
+        tt.resize(mb);
+        tt
+    }
+
+    // Resizing clears the table.
+    pub fn resize(&mut self, mb: usize) {
+        let count = (mb * 1024 * 1024 / std::mem::size_of::<Bucket>()).max(1);
+        self.buckets = Vec::new(); // free the old table before allocating
+        self.buckets = (0..count).map(|_| Bucket::default()).collect();
+    }
+
+    pub fn size_mb(&self) -> usize {
+        self.buckets.len() * std::mem::size_of::<Bucket>() / (1024 * 1024)
+    }
+
+    pub fn clear(&self) {
+        for bucket in &self.buckets {
+            for entry in &bucket.entries {
+                entry.key.store(0, Ordering::Relaxed);
+                entry.data.store(0, Ordering::Relaxed);
+            }
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    // Entries from earlier searches become the first to be replaced.
+    pub fn new_search(&self) {
+        let age = self.age.load(Ordering::Relaxed);
+        self.age.store((age + 1) % AGE_CYCLE, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        // Maps the key onto the table without needing a power-of-two size.
+        let index = (key as u128 * self.buckets.len() as u128) >> 64;
+        &self.buckets[index as usize]
+    }
+
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TtEntry> {
+        for entry in &self.bucket(key).entries {
+            let data = entry.data.load(Ordering::Relaxed);
+            if entry.key.load(Ordering::Relaxed) ^ data != key {
+                continue;
+            }
+            let bound = unpack_bound(data)?;
+            return Some(TtEntry {
+                mv: data as u16,
+                score: score_from_tt((data >> 16) as u16 as i16 as i32, ply),
+                depth: unpack_depth(data),
+                bound,
+            });
+        }
+        None
+    }
+
+    pub fn store(&self, key: u64, ply: usize, mv: u16, score: i32, depth: i32, bound: Bound) {
+        let age = self.age.load(Ordering::Relaxed);
+        let entries = &self.bucket(key).entries;
+
+        // Overwrite this position's own entry if it has one, or else an empty
+        // one, or else whichever is shallowest once older searches' entries
+        // are marked down.
+        let mut replace = &entries[0];
+        let mut worst = i32::MAX;
+        for entry in entries {
+            let data = entry.data.load(Ordering::Relaxed);
+            if entry.key.load(Ordering::Relaxed) ^ data == key || unpack_bound(data).is_none() {
+                replace = entry;
+                break;
+            }
+            let stale = (AGE_CYCLE + age - unpack_age(data)) % AGE_CYCLE;
+            let value = unpack_depth(data) - 8 * stale as i32;
+            if value < worst {
+                worst = value;
+                replace = entry;
+            }
+        }
+
+        // Keep the old best move rather than none at all.
+        let old = replace.data.load(Ordering::Relaxed);
+        let mv = if mv == NULL_MOVE && replace.key.load(Ordering::Relaxed) ^ old == key {
+            old as u16
+        } else {
+            mv
+        };
+
+        let data = pack(mv, score_to_tt(score, ply), depth, bound, age);
+        replace.key.store(key ^ data, Ordering::Relaxed);
+        replace.data.store(data, Ordering::Relaxed);
+    }
+
+    // How full the table is, in permille, sampled from the first thousand
+    // entries and counting only those from the current search.
+    pub fn hashfull(&self) -> usize {
+        let age = self.age.load(Ordering::Relaxed);
+        let mut full = 0;
+        let mut sampled = 0;
+        for bucket in self.buckets.iter().take(1000 / BUCKET_SIZE) {
+            for entry in &bucket.entries {
+                let data = entry.data.load(Ordering::Relaxed);
+                if unpack_bound(data).is_some() && unpack_age(data) == age {
+                    full += 1;
+                }
+                sampled += 1;
+            }
+        }
+        full * 1000 / sampled
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
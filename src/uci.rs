@@ -0,0 +1,356 @@
+use crate::moves::*;
+use crate::perft::*;
+use crate::position::Position;
+use crate::search::*;
+use crate::timeman::*;
+use crate::variant::*;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const NAME: &str = "rust-experiments";
+const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+const DEFAULT_HASH: usize = 16;
+const MAX_HASH: usize = 65536;
+const MAX_THREADS: usize = 256;
+const MAX_MULTIPV: usize = 256;
+
+// The words that start each part of a go command.
+const GO_KEYWORDS: [&str; 13] = [
+    "searchmoves", "ponder", "wtime", "btime", "winc", "binc", "movestogo", "depth", "nodes", "mate", "movetime",
+    "infinite", "perft",
+];
+
+// Where a front end's replies go, one line at a time: stdout, unless
+// something else wants to read them.
+pub type Output = Arc<dyn Fn(&str) + Send + Sync>;
+
+pub fn stdout() -> Output {
+    Arc::new(|line| println!("{}", line))
+}
+
+// The engine side of the Universal Chess Interface.  Commands are read from
+// stdin, and searches run on their own thread so that stop can reach them.
+pub struct Uci {
+    pos: Position,
+    history: Vec<u64>, // hashes of the positions before pos
+    search: Option<Search>, // lent to the search thread while it runs
+    thread: Option<JoinHandle<Search>>,
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    // While pondering or searching infinitely, bestmove has to wait for stop
+    // or ponderhit even if the search finishes first.
+    waiting: Arc<AtomicBool>,
+    multipv: usize,
+    move_overhead: Duration,
+    output: Output,
+}
+
+impl Default for Uci {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Uci {
+    pub fn new() -> Uci {
+        Self::with_output(stdout())
+    }
+
+    pub fn with_output(output: Output) -> Uci {
+        let mut search = Search::new();
+        search.resize_tt(DEFAULT_HASH);
+        let mut pos = Position::new();
+        pos.from_fen(STARTPOS);
+        Uci {
+            pos,
+            history: Vec::new(),
+            stop: search.stop_flag(),
+            ponder: search.ponder_flag(),
+            search: Some(search),
+            thread: None,
+            waiting: Arc::new(AtomicBool::new(false)),
+            multipv: 1,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            output,
+        }
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.pos
+    }
+
+    pub fn history(&self) -> &[u64] {
+        &self.history
+    }
+
+    fn send(&self, line: &str) {
+        (self.output)(line);
+    }
+
+    // Handle one command, returning false once it's time to quit.
+    pub fn command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(&command) = tokens.first() else {
+            return true;
+        };
+        let args = &tokens[1..];
+
+        match command {
+            "uci" => {
+                self.send(&format!("id name {}", NAME));
+                self.send(&format!("id author the {} authors", NAME));
+                self.send(&format!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH, MAX_HASH));
+                self.send(&format!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS));
+                self.send(&format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTIPV));
+                self.send("option name Ponder type check default false");
+                self.send(&format!(
+                    "option name Move Overhead type spin default {} min 0 max {}",
+                    DEFAULT_MOVE_OVERHEAD.as_millis(),
+                    MAX_MOVE_OVERHEAD
+                ));
+                self.send("uciok");
+            }
+            "isready" => self.send("readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.search_mut().clear();
+            }
+            "setoption" => {
+                self.stop_search();
+                self.set_option(args);
+            }
+            "position" => {
+                self.stop_search();
+                self.set_position(args);
+            }
+            "go" => self.go(args),
+            "stop" => self.stop_search(),
+            "ponderhit" => self.ponderhit(),
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            _ => self.send(&format!("info string unknown command {}", command)),
+        }
+        true
+    }
+
+    fn search_mut(&mut self) -> &mut Search {
+        self.search.as_mut().expect("no search while one is running")
+    }
+
+    // setoption name <id> [value <x>], where the name can have spaces in it.
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args.iter().position(|&arg| arg == "value").unwrap_or(args.len());
+        let name = args[..value_at].iter().skip(1).copied().collect::<Vec<_>>().join(" ");
+        let value = args.get(value_at + 1..).unwrap_or(&[]).join(" ");
+        let number = value.parse::<usize>().ok();
+
+        match (name.to_lowercase().as_str(), number) {
+            ("hash", Some(mb)) => self.search_mut().resize_tt(mb.clamp(1, MAX_HASH)),
+            ("threads", Some(threads)) => self.search_mut().set_threads(threads.clamp(1, MAX_THREADS)),
+            ("multipv", Some(lines)) => self.multipv = lines.clamp(1, MAX_MULTIPV),
+            ("ponder", _) => {}
+            ("move overhead", Some(millis)) => {
+                self.move_overhead = Duration::from_millis((millis as u64).min(MAX_MOVE_OVERHEAD))
+            }
+            _ => self.send(&format!("info string unknown option {} {}", name, value)),
+        }
+    }
+
+    // position startpos|fen <fen> [moves <move>...]
+    fn set_position(&mut self, args: &[&str]) {
+        let moves_at = args.iter().position(|&arg| arg == "moves").unwrap_or(args.len());
+        match args.first() {
+            Some(&"startpos") => self.pos.from_fen(STARTPOS),
+            Some(&"fen") => {
+                // A bad FEN keeps the position from before.
+                if let Err(err) = self.pos.try_from_fen(&args[1..moves_at].join(" ")) {
+                    self.send(&format!("info string {}", err));
+                    return;
+                }
+            }
+            _ => {
+                self.send("info string expected startpos or fen");
+                return;
+            }
+        }
+
+        self.history.clear();
+        for &s in args.iter().skip(moves_at + 1) {
+            let Some(mv) = self.pos.parse_move(s) else {
+                self.send(&format!("info string illegal move {}", s));
+                return;
+            };
+            self.history.push(self.pos.hash());
+            self.pos.make_move(mv);
+        }
+    }
+
+    fn go(&mut self, args: &[&str]) {
+        self.stop_search();
+
+        let mut limits = Limits {
+            move_overhead: self.move_overhead,
+            multipv: self.multipv,
+            ..Limits::default()
+        };
+        let mut time = [None, None]; // time left, by color
+        let mut increment = [Duration::ZERO; 2];
+        let mut movestogo = None;
+        let mut infinite = false;
+        let mut ponder = false;
+
+        let mut i = 0;
+        while i < args.len() {
+            let number = args.get(i + 1).and_then(|arg| arg.parse::<u64>().ok());
+            let millis = number.map(Duration::from_millis);
+            match args[i] {
+                "perft" => {
+                    self.start_perft(number.unwrap_or(1) as u32);
+                    return;
+                }
+                "depth" => limits.depth = number.unwrap_or(1) as u32,
+                "nodes" => limits.nodes = number.unwrap_or(u64::MAX),
+                "mate" => limits.mate = number.map(|moves| moves as u32),
+                "movetime" => limits.movetime = millis,
+                "wtime" => time[0] = millis,
+                "btime" => time[1] = millis,
+                "winc" => increment[0] = millis.unwrap_or_default(),
+                "binc" => increment[1] = millis.unwrap_or_default(),
+                "movestogo" => movestogo = number.map(|moves| moves as u32),
+                "infinite" => infinite = true,
+                "ponder" => ponder = true,
+                "searchmoves" => {
+                    // Every move that follows, up to the next keyword.  With
+                    // none of them legal there's nothing to restrict the
+                    // search to, so say so rather than quietly search all.
+                    while let Some(&s) = args.get(i + 1).filter(|s| !GO_KEYWORDS.contains(s)) {
+                        match self.pos.parse_move(s) {
+                            Some(mv) => limits.searchmoves.push(mv),
+                            None => self.send(&format!("info string illegal searchmoves move {}", s)),
+                        }
+                        i += 1;
+                    }
+                    if limits.searchmoves.is_empty() {
+                        self.send("info string no legal searchmoves, searching every move");
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        let us = self.pos.us() as usize;
+        limits.clock = time[us].map(|time| Clock {
+            time,
+            increment: increment[us],
+            movestogo,
+        });
+
+        // Pondering searches on the opponent's time, so the clock only starts
+        // with ponderhit.
+        self.ponder.store(ponder, Ordering::Relaxed);
+
+        self.start_search(limits, infinite || ponder);
+    }
+
+    fn start_search(&mut self, limits: Limits, wait: bool) {
+        let mut search = self.search.take().expect("a search is already running");
+        let pos = self.pos;
+        let history = self.history.clone();
+        let stop = self.stop.clone();
+        let waiting = self.waiting.clone();
+        let output = self.output.clone();
+        self.stop.store(false, Ordering::Relaxed);
+        self.waiting.store(wait, Ordering::Relaxed);
+
+        self.thread = Some(thread::spawn(move || {
+            let result = search.search(&pos, &history, limits, |info| output(&info_line(info, &pos)));
+            while waiting.load(Ordering::Relaxed) && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            let mut line = format!("bestmove {}", uci_move(result.best_move(), &pos));
+            if let Some(&reply) = result.pv.get(1) {
+                line.push_str(&format!(" ponder {}", mv_to_str(reply, pos.chess960)));
+            }
+            output(&line);
+            search
+        }));
+    }
+
+    // Perft runs on the search thread too, so that commands are still read
+    // while it counts, though stop has to wait for it to finish.
+    fn start_perft(&mut self, depth: u32) {
+        let search = self.search.take().expect("a search is already running");
+        let mut pos = self.pos;
+        let output = self.output.clone();
+        self.thread = Some(thread::spawn(move || {
+            divide_to::<Standard, _>(depth, &mut pos, |line| output(line));
+            search
+        }));
+    }
+
+    // Stop any search that's running and wait for it to report its move.
+    fn stop_search(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            self.search = Some(thread.join().expect("search thread panicked"));
+        }
+    }
+
+    // The opponent played the expected move, so the ponder search carries on
+    // as a normal one, now against the clock.
+    fn ponderhit(&mut self) {
+        self.ponder.store(false, Ordering::Relaxed);
+        self.waiting.store(false, Ordering::Relaxed);
+    }
+}
+
+fn uci_move(mv: u16, pos: &Position) -> String {
+    if mv == NULL_MOVE {
+        "0000".to_string()
+    } else {
+        mv_to_str(mv, pos.chess960)
+    }
+}
+
+pub fn score_to_str(score: i32) -> String {
+    match mate_in(score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", score),
+    }
+}
+
+pub fn info_line(info: &SearchInfo, pos: &Position) -> String {
+    let pv: Vec<String> = info.pv.iter().map(|&mv| mv_to_str(mv, pos.chess960)).collect();
+    format!(
+        "info depth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+        info.depth,
+        info.multipv,
+        score_to_str(info.score),
+        info.nodes,
+        info.nps,
+        info.hashfull,
+        info.time.as_millis(),
+        pv.join(" ")
+    )
+}
+
+pub fn run() {
+    let mut uci = Uci::new();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !uci.command(&line) {
+            return;
+        }
+    }
+    uci.stop_search();
+}
@@ -0,0 +1,112 @@
+use crate::moves::*;
+use crate::position::{IllegalFen, Position};
+use crate::things::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Win(Color),
+    Draw,
+}
+
+// Variant-specific parts of a FEN string, separated from the standard fields
+// by Position::from_variant_fen().
+#[derive(Clone, Copy, Default)]
+pub struct FenExtensions<'a> {
+    pub pocket: Option<&'a str>, // pieces in hand, e.g. "QNp" from "[QNp]"
+    pub checks: Option<&'a str>, // check counters, e.g. "+2+1"
+}
+
+// The rules which differ from one chess variant to the next.  MoveGen,
+// make/unmake, FEN parsing and Position::outcome() are generic over a
+// Variant and call these hooks at the relevant points.  Every hook defaults
+// to standard chess, and since they are resolved at compile time the
+// Standard variant costs nothing.
+pub trait Variant {
+    // Variants where a king may be left attacked, or there's no check at all,
+    // can't use the pin and check based legal move generator.  For those,
+    // MoveGen generates pseudo-legal moves and keeps the ones is_legal()
+    // accepts.
+    const PSEUDO_LEGAL: bool = false;
+
+    // Pieces can be dropped from the pocket (crazyhouse and bughouse).
+    const DROPS: bool = false;
+
+    // Pawns can promote to a king as well (antichess).
+    const KING_PROMOTION: bool = false;
+
+    // Pawns on their first rank can advance two squares (horde).
+    const FIRST_RANK_DOUBLE_PUSH: bool = false;
+
+    fn is_legal(_pos: &Position, _mv: u16) -> bool {
+        true
+    }
+
+    // Extra filtering of the legal moves, e.g. forced captures.
+    fn filter_moves(_pos: &Position, _moves: &mut MoveList) {}
+
+    // Called at the end of make_move(), once the turn has passed to the other
+    // side.  Anything after_move() changes has to be recorded in the Undo so
+    // before_unmake() can put it back.
+    fn after_move(_pos: &mut Position, _undo: &mut Undo) {}
+
+    // Called at the start of unmake_move(), before the standard work.
+    fn before_unmake(_pos: &mut Position, _undo: &Undo) {}
+
+    // Game end conditions other than running out of moves, e.g. a king
+    // reaching the centre.  No moves are generated once the game is over.
+    fn variant_outcome(_pos: &Position) -> Option<Outcome> {
+        None
+    }
+
+    // Result when the side to move has no legal moves.
+    fn no_moves_outcome(pos: &Position) -> Outcome {
+        if pos.calc_checkers() != 0 {
+            Outcome::Win(pos.enemy())
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    fn parse_fen_extensions(_pos: &mut Position, _ext: &FenExtensions) {}
+
+    // Turns away extensions parse_fen_extensions() can't make sense of, for
+    // Position::try_from_variant_fen().
+    fn check_fen_extensions(_ext: &FenExtensions) -> Result<(), IllegalFen> {
+        Ok(())
+    }
+
+    // Appended to the board field of the FEN, e.g. "[QNp]".
+    fn fen_board_suffix(_pos: &Position) -> String {
+        String::new()
+    }
+
+    // Appended to the FEN after the move counters, e.g. " +2+1".
+    fn fen_suffix(_pos: &Position) -> String {
+        String::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Standard;
+
+impl Variant for Standard {}
+
+impl Position {
+    // None while the game is still going.
+    pub fn outcome<V: Variant>(&self) -> Option<Outcome> {
+        if let Some(outcome) = V::variant_outcome(self) {
+            return Some(outcome);
+        }
+
+        let mut move_generator = MoveGen::<V>::for_variant(self);
+        move_generator.gen_legal_moves();
+        if move_generator.moves.is_empty() {
+            return Some(V::no_moves_outcome(self));
+        }
+
+        if self.halfmove_clock() >= 100 {
+            return Some(Outcome::Draw);
+        }
+        None
+    }
+}
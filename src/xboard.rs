@@ -0,0 +1,407 @@
+use crate::moves::*;
+use crate::position::Position;
+use crate::search::*;
+use crate::things::*;
+use crate::timeman::*;
+use crate::uci::{stdout, Output};
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const NAME: &str = "rust-experiments";
+const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+const MAX_CORES: usize = 256;
+
+// XBoard shows mate scores past this as mates.
+const XBOARD_MATE: i32 = 100000;
+
+// The engine side of the Chess Engine Communication Protocol, as spoken by
+// XBoard and WinBoard.  Like the UCI front end, searches run on their own
+// thread, but here the engine keeps the game itself: it plays its own moves
+// and takes them back on request.
+pub struct XBoard {
+    pos: Position,
+    history: Vec<u64>, // hashes of the positions before pos
+    undos: Vec<Undo>,  // to take back each move played
+    search: Option<Search>,
+    thread: Option<JoinHandle<(Search, Option<u16>)>>,
+    stop: Arc<AtomicBool>,
+    abort: Arc<AtomicBool>, // stop without playing the move found
+
+    engine: Color, // the side the engine plays, unless in force mode
+    force: bool,
+    analyzing: bool,
+    post: bool,
+
+    // Time controls: moves per session (0 for the whole game), the increment,
+    // a fixed time per move, and a depth limit.  Sessions are counted from
+    // the ply the time control was set, or the game started, at.
+    moves_per_session: u32,
+    session_start: usize,
+    increment: Duration,
+    fixed_time: Option<Duration>,
+    max_depth: Option<u32>,
+    time_left: Duration,
+    move_overhead: Duration,
+    cores: usize, // threads to search with
+    output: Output,
+}
+
+impl Default for XBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XBoard {
+    pub fn new() -> XBoard {
+        Self::with_output(stdout())
+    }
+
+    pub fn with_output(output: Output) -> XBoard {
+        let search = Search::new();
+        let mut pos = Position::new();
+        pos.from_fen(STARTPOS);
+        XBoard {
+            pos,
+            history: Vec::new(),
+            undos: Vec::new(),
+            stop: search.stop_flag(),
+            search: Some(search),
+            thread: None,
+            abort: Arc::new(AtomicBool::new(false)),
+            engine: Black,
+            force: false,
+            analyzing: false,
+            post: false,
+            moves_per_session: 0,
+            session_start: 0,
+            increment: Duration::ZERO,
+            fixed_time: None,
+            max_depth: None,
+            time_left: Duration::from_secs(300),
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            cores: 1,
+            output,
+        }
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.pos
+    }
+
+    pub fn history(&self) -> &[u64] {
+        &self.history
+    }
+
+    fn send(&self, line: &str) {
+        (self.output)(line);
+    }
+
+    // Handle one command, returning false once it's time to quit.
+    pub fn command(&mut self, line: &str) -> bool {
+        // A move the engine found while this command was on its way has
+        // already been announced, so it's part of the game.
+        if self.thread.as_ref().is_some_and(|thread| thread.is_finished()) {
+            self.finish_search();
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(&command) = tokens.first() else {
+            return true;
+        };
+        let args = &tokens[1..];
+        let number = |i: usize| args.get(i).and_then(|arg| arg.parse::<u64>().ok());
+
+        match command {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name"
+            | "rating" | "ics" | "." => {}
+            "protover" => {
+                self.send("feature done=0");
+                self.send(&format!(
+                    "feature myname=\"{}\" setboard=1 usermove=1 ping=1 analyze=1 colors=0 sigint=0 sigterm=0 reuse=1 smp=1",
+                    NAME
+                ));
+                self.send(&format!(
+                    "feature option=\"Move Overhead -spin {} 0 {}\"",
+                    DEFAULT_MOVE_OVERHEAD.as_millis(),
+                    MAX_MOVE_OVERHEAD
+                ));
+                self.send("feature done=1");
+            }
+            "ping" => self.send(&format!("pong {}", args.first().unwrap_or(&""))),
+            "new" => {
+                self.abort_search();
+                self.pos.from_fen(STARTPOS);
+                self.history.clear();
+                self.undos.clear();
+                self.session_start = 0;
+                self.engine = Black;
+                self.force = false;
+                self.max_depth = None;
+                self.search_mut().clear();
+                self.restart_analysis();
+            }
+            "setboard" => {
+                self.abort_search();
+                // A bad FEN leaves the game as it was.
+                match self.pos.try_from_fen(&args.join(" ")) {
+                    Ok(()) => {
+                        self.history.clear();
+                        self.undos.clear();
+                        self.session_start = 0;
+                    }
+                    Err(err) => self.send(&format!("tellusererror Illegal position: {}", err.0)),
+                }
+                self.restart_analysis();
+            }
+            "usermove" => self.user_move(args.first().unwrap_or(&"")),
+            "go" => {
+                self.abort_search();
+                self.force = false;
+                self.engine = self.pos.us();
+                self.think();
+            }
+            "force" => {
+                self.abort_search();
+                self.force = true;
+            }
+            "result" => {
+                self.abort_search();
+                self.force = true;
+            }
+            "?" => self.finish_search(),
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "level" => {
+                // level MPS BASE INC, where BASE is minutes or minutes:seconds.
+                self.moves_per_session = number(0).unwrap_or(0) as u32;
+                self.session_start = self.undos.len();
+                let base = args.get(1).unwrap_or(&"0");
+                let (minutes, seconds) = base.split_once(':').unwrap_or((base, "0"));
+                let minutes = minutes.parse::<u64>().unwrap_or(0);
+                let seconds = minutes.saturating_mul(60).saturating_add(seconds.parse::<u64>().unwrap_or(0));
+                self.time_left = Duration::from_secs(seconds);
+                // The increment may have a fraction, but not be negative or
+                // infinite.
+                let increment = args.get(2).and_then(|arg| arg.parse::<f64>().ok());
+                self.increment = increment.and_then(|secs| Duration::try_from_secs_f64(secs).ok()).unwrap_or_default();
+                self.fixed_time = None;
+            }
+            "st" => self.fixed_time = number(0).map(Duration::from_secs),
+            "cores" => self.cores = number(0).unwrap_or(1).clamp(1, MAX_CORES as u64) as usize,
+            "sd" => self.max_depth = number(0).map(|depth| depth as u32),
+            "time" => self.time_left = Duration::from_millis(number(0).unwrap_or(0).saturating_mul(10)),
+            "otim" => {}
+            "option" => {
+                // option NAME=VALUE, where the name can have spaces in it.
+                let option = args.join(" ");
+                match option.split_once('=').map(|(name, value)| (name, value.parse::<u64>())) {
+                    Some(("Move Overhead", Ok(millis))) => {
+                        self.move_overhead = Duration::from_millis(millis.min(MAX_MOVE_OVERHEAD))
+                    }
+                    _ => self.send(&format!("Error (unknown option): {}", option)),
+                }
+            }
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "analyze" => {
+                self.abort_search();
+                self.analyzing = true;
+                self.restart_analysis();
+            }
+            "exit" => {
+                self.abort_search();
+                self.analyzing = false;
+            }
+            "quit" => {
+                self.abort_search();
+                return false;
+            }
+            _ => {
+                // Without usermove=1 a move comes on its own.
+                if self.pos.parse_move(command).is_some() {
+                    self.user_move(command);
+                } else {
+                    self.send(&format!("Error (unknown command): {}", command));
+                }
+            }
+        }
+        true
+    }
+
+    fn search_mut(&mut self) -> &mut Search {
+        self.search.as_mut().expect("no search while one is running")
+    }
+
+    fn user_move(&mut self, s: &str) {
+        self.abort_search();
+        let Some(mv) = self.pos.parse_move(s) else {
+            self.send(&format!("Illegal move: {}", s));
+            return;
+        };
+        self.play(mv);
+        if self.analyzing {
+            self.restart_analysis();
+        } else if !self.force && self.pos.us() == self.engine {
+            self.think();
+        }
+    }
+
+    fn play(&mut self, mv: u16) {
+        self.history.push(self.pos.hash());
+        self.undos.push(self.pos.make_move(mv));
+    }
+
+    fn take_back(&mut self, moves: usize) {
+        self.abort_search();
+        for _ in 0..moves.min(self.undos.len()) {
+            let undo = self.undos.pop().unwrap();
+            self.pos.unmake_move(undo);
+            self.history.pop();
+        }
+        self.restart_analysis();
+    }
+
+    // Search for the engine's move, to be played when the search finishes.
+    fn think(&mut self) {
+        if self.report_game_over() {
+            return;
+        }
+        self.start_search(self.limits(), true);
+    }
+
+    // What the engine's next move is allowed to take, given the time
+    // controls.
+    pub fn limits(&self) -> Limits {
+        let mut limits = Limits {
+            depth: self.max_depth.unwrap_or(Limits::default().depth),
+            move_overhead: self.move_overhead,
+            ..Limits::default()
+        };
+        match self.fixed_time {
+            Some(time) => limits.movetime = Some(time.saturating_sub(limits.move_overhead)),
+            None => {
+                // Every other ply since the session started was ours.
+                let played = (self.undos.len().saturating_sub(self.session_start) / 2) as u32;
+                let movestogo = match self.moves_per_session {
+                    0 => None,
+                    mps => Some(mps - played % mps),
+                };
+                limits.clock = Some(Clock {
+                    time: self.time_left,
+                    increment: self.increment,
+                    movestogo,
+                });
+            }
+        }
+        limits
+    }
+
+    fn restart_analysis(&mut self) {
+        if self.analyzing {
+            self.abort_search();
+            self.start_search(Limits::default(), false);
+        }
+    }
+
+    fn start_search(&mut self, limits: Limits, play: bool) {
+        let mut search = self.search.take().expect("a search is already running");
+        if search.threads() != self.cores {
+            search.set_threads(self.cores);
+        }
+        let pos = self.pos;
+        let history = self.history.clone();
+        let abort = self.abort.clone();
+        let post = self.post || self.analyzing;
+        let output = self.output.clone();
+        self.stop.store(false, Ordering::Relaxed);
+        self.abort.store(false, Ordering::Relaxed);
+
+        self.thread = Some(thread::spawn(move || {
+            let result = search.search(&pos, &history, limits, |info| {
+                if post {
+                    output(&thinking_line(info, &pos));
+                }
+            });
+
+            // Whatever's announced here is played; finish_search() makes it
+            // on the board.
+            if !play || abort.load(Ordering::Relaxed) || result.best_move() == NULL_MOVE {
+                return (search, None);
+            }
+            output(&format!("move {}", mv_to_str(result.best_move(), pos.chess960)));
+            (search, Some(result.best_move()))
+        }));
+    }
+
+    // Stop any search, playing the move it found if it's to make one.
+    fn finish_search(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            let (search, mv) = thread.join().expect("search thread panicked");
+            self.search = Some(search);
+            if let Some(mv) = mv {
+                self.play(mv);
+                self.report_game_over();
+            }
+        }
+    }
+
+    // Stop any search without it making a move, unless it already has.
+    fn abort_search(&mut self) {
+        self.abort.store(true, Ordering::Relaxed);
+        self.finish_search();
+    }
+
+    // Announce checkmate or stalemate, if the game's over.
+    fn report_game_over(&self) -> bool {
+        let mut move_generator = MoveGen::new(&self.pos);
+        move_generator.gen_legal_moves();
+        if !move_generator.moves.is_empty() {
+            return false;
+        }
+        if self.pos.calc_checkers() == 0 {
+            self.send("1/2-1/2 {Stalemate}");
+        } else if self.pos.us() == White {
+            self.send("0-1 {Black mates}");
+        } else {
+            self.send("1-0 {White mates}");
+        }
+        true
+    }
+}
+
+// ply score time nodes pv, with time in centiseconds.
+fn thinking_line(info: &SearchInfo, pos: &Position) -> String {
+    let score = match mate_in(info.score) {
+        Some(moves) if moves > 0 => XBOARD_MATE + moves,
+        Some(moves) => -XBOARD_MATE + moves,
+        None => info.score,
+    };
+    let pv: Vec<String> = info.pv.iter().map(|&mv| mv_to_str(mv, pos.chess960)).collect();
+    format!(
+        "{} {} {} {} {}",
+        info.depth,
+        score,
+        info.time.as_millis() / 10,
+        info.nodes,
+        pv.join(" ")
+    )
+}
+
+pub fn run() {
+    let mut xboard = XBoard::new();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !xboard.command(&line) {
+            return;
+        }
+    }
+    xboard.abort_search();
+}
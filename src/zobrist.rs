@@ -0,0 +1,53 @@
+use crate::square::*;
+use crate::things::*;
+
+// Random keys for Zobrist hashing, generated at compile time with splitmix64
+// so they're the same on every run.  There's a key for each piece of each
+// color on each square, then one for black to move, one for each combination
+// of castle rights and one for each file an en passant capture could be on.
+const PIECE_KEYS: usize = 0;
+const SIDE_KEY: usize = PIECE_KEYS + NUM_COLORS * NUM_PIECES * NUM_SQUARES;
+const CASTLE_KEYS: usize = SIDE_KEY + 1;
+const EP_KEYS: usize = CASTLE_KEYS + 16;
+const NUM_KEYS: usize = EP_KEYS + NUM_FILES;
+
+const KEYS: [u64; NUM_KEYS] = build_keys();
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn build_keys() -> [u64; NUM_KEYS] {
+    let mut keys = [0; NUM_KEYS];
+    let mut state = 0;
+    let mut i = 0;
+    while i < NUM_KEYS {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys[i] = key;
+        i += 1;
+    }
+    // No castle rights at all hashes as nothing.
+    keys[CASTLE_KEYS] = 0;
+    keys
+}
+
+pub fn piece_key(piece: Piece, color: Color, sq: u8) -> u64 {
+    KEYS[PIECE_KEYS + (color as usize * NUM_PIECES + piece as usize) * NUM_SQUARES + sq as usize]
+}
+
+pub fn side_key() -> u64 {
+    KEYS[SIDE_KEY]
+}
+
+pub fn castle_key(castle: u8) -> u64 {
+    KEYS[CASTLE_KEYS + castle as usize]
+}
+
+pub fn ep_key(ep: u8) -> u64 {
+    KEYS[EP_KEYS + file_of(ep) as usize]
+}